@@ -15,6 +15,7 @@
 
 #include "proc.h"
 #include "ram.h"
+#include "system.h"
 #include "cpu.h"
 #include "temp.h"
 #include "dispatcher.h"
@@ -97,12 +98,19 @@ void * dispatcher_task(void *params)
   }
 }
 
+/* Every file starts with "Seq: N" and ends with "End: N" so that readers
+ * can tell a complete snapshot from one that is still being written */
 void * resources_task(void *params)
 {
+  uint32_t snapshot_seq = 0;
+
   init_cpu_ctx();
 
   while(0xDEADBEEF) {
+    snapshot_seq++;
+
     read_ram_usage();
+    read_system_info();
     read_user_processes();
     update_cpu_ctx();
     read_ext_temp();
@@ -115,7 +123,9 @@ void * resources_task(void *params)
       break;
     }
 
+    fprintf(ram, "Seq: %u\n", snapshot_seq);
     print_ram_usage(ram);
+    fprintf(ram, "End: %u\n", snapshot_seq);
     fclose(ram);
 
     FILE *cpu = fopen("/tmp/cpu_tmp", "w+");
@@ -124,7 +134,9 @@ void * resources_task(void *params)
       break;
     }
 
+    fprintf(cpu, "Seq: %u\n", snapshot_seq);
     print_cpu_ctx(cpu);
+    fprintf(cpu, "End: %u\n", snapshot_seq);
     fclose(cpu);
 
     FILE *proc = fopen("/tmp/proc_tmp", "w+");
@@ -133,7 +145,9 @@ void * resources_task(void *params)
       break;
     }
 
+    fprintf(proc, "Seq: %u\n", snapshot_seq);
     print_procs(proc);
+    fprintf(proc, "End: %u\n", snapshot_seq);
     fclose(proc);
 
     FILE *temp = fopen("/tmp/ext_temp_tmp", "w+");
@@ -142,13 +156,27 @@ void * resources_task(void *params)
       break;
     }
 
+    fprintf(temp, "Seq: %u\n", snapshot_seq);
     save_ext_temp(temp);
+    fprintf(temp, "End: %u\n", snapshot_seq);
     fclose(temp);
 
+    FILE *sys_info = fopen("/tmp/system_tmp", "w+");
+    if (sys_info == NULL) {
+      perror("fopen system_tmp:");
+      break;
+    }
+
+    fprintf(sys_info, "Seq: %u\n", snapshot_seq);
+    print_system_info(sys_info);
+    fprintf(sys_info, "End: %u\n", snapshot_seq);
+    fclose(sys_info);
+
     rename("/tmp/ram_tmp", "/tmp/ram");
     rename("/tmp/cpu_tmp", "/tmp/cpu");
     rename("/tmp/proc_tmp", "/tmp/proc");
     rename("/tmp/ext_temp_tmp", "/tmp/ext_temp");
+    rename("/tmp/system_tmp", "/tmp/system");
 
     usleep(MS_TO_US(MONITOR_TIMEOUT_MS));
   }
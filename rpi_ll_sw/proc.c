@@ -52,6 +52,29 @@ static inline int get_proc_utime(proc_ctx_t *proc_ctx, char *line)
   return result;
 }
 
+// Field 22 of the stat line: start time in clock ticks after boot. Fields
+// are counted from the closing parenthesis of comm, which may hold spaces.
+static inline int get_proc_start_time(proc_ctx_t *proc_ctx, const char *line)
+{
+  const char *rest = strrchr(line, ')');
+  unsigned long long start_time;
+
+  if (rest == NULL) {
+    return -1;
+  }
+
+  if (sscanf(rest + 1,
+        // State, then fields 4 to 21 skipped
+        " %*c %*s %*s %*s %*s %*s %*s %*s %*s %*s"
+        " %*s %*s %*s %*s %*s %*s %*s %*s %*s %llu",
+        &start_time) != 1) {
+    return -1;
+  }
+  proc_ctx->start_time = start_time;
+
+  return 0;
+}
+
 static inline void save_old_utime(void) {
   utime_ctxs = (utime_ctx_t *)reallocarray(utime_ctxs, proc_count, sizeof(utime_ctx_t));
 
@@ -185,6 +208,12 @@ int get_proc_info(const char *status_path, const char *stat_path, proc_ctx_t *pr
     }
   }
 
+  while ((getline(&line, &len, status_fd)) != -1) {
+    if (sscanf(line, "PPid: %u", &(proc_ctx->ppid)) == 1) {
+      break;
+    }
+  }
+
   while ((getline(&line, &len, status_fd)) != -1) {
     if (sscanf(line, "Uid: %u", &(proc_ctx->uid)) == 1) {
       break;
@@ -215,6 +244,12 @@ int get_proc_info(const char *status_path, const char *stat_path, proc_ctx_t *pr
     }
   }
 
+  while ((getline(&line, &len, status_fd)) != -1) {
+    if (sscanf(line, "VmRSS: %u kB", &(proc_ctx->vm_rss_kB)) == 1) {
+      break;
+    }
+  }
+
   while ((getline(&line, &len, status_fd)) != -1) {
     if (sscanf(line, "VmSwap: %u kB", &(proc_ctx->vm_swap_kB)) == 1) {
       break;
@@ -253,6 +288,9 @@ int get_proc_info(const char *status_path, const char *stat_path, proc_ctx_t *pr
   getline(&line, &len, stat_fd);
 
   get_proc_utime(proc_ctx, line);
+  if (get_proc_start_time(proc_ctx, line) == -1) {
+    proc_ctx->start_time = 0;
+  }
   proc_ctx->utime_old = get_old_utime(proc_ctx->proc_id);;
 
   fclose(stat_fd);
@@ -274,9 +312,14 @@ void print_procs(FILE *fd) {
 int print_proc_ctx(proc_ctx_t proc_ctx, FILE *fd) {
   fprintf(fd, "Proc: %u %s\n", proc_ctx.proc_id, proc_ctx.proc_name);
   fprintf(fd, "State: %s\n", proc_ctx.state);
+  fprintf(fd, "PPid: %u\n", proc_ctx.ppid);
+  if (proc_ctx.start_time != 0) {
+    fprintf(fd, "Start_time: %llu\n", (unsigned long long)proc_ctx.start_time);
+  }
   fprintf(fd, "User: %u %s\n", proc_ctx.uid, proc_ctx.user);
   fprintf(fd, "Group: %u %s\n", proc_ctx.gid, proc_ctx.group);
-  fprintf(fd, "Memory: %u/%u kB\n", proc_ctx.vm_size_kB, proc_ctx.vm_max_size_kB);
+  // Resident and virtual size, as the server expects
+  fprintf(fd, "Memory: %u/%u kB\n", proc_ctx.vm_rss_kB, (uint32_t)proc_ctx.vm_size_kB);
   fprintf(fd, "Swap: %u kB\n", proc_ctx.vm_swap_kB);
   fprintf(fd, "Threads: %u\n", proc_ctx.threads);
   fprintf(fd, "Max_cpus: %u\n", proc_ctx.max_cpus);
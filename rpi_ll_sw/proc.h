@@ -9,6 +9,8 @@ typedef struct
 
   char state[16];
 
+  uint32_t ppid;
+
   uint32_t uid;
   char user[64];
 
@@ -20,10 +22,13 @@ typedef struct
   uint64_t vm_max_size_kB;
   uint64_t vm_size_kB;
   uint64_t vm_swap_kB;
+  uint32_t vm_rss_kB;
 
   uint32_t threads;
   uint32_t max_cpus;
 
+  uint64_t start_time;
+
   uint64_t utime_new;
   uint64_t utime_old;
 } proc_ctx_t;
@@ -1,9 +1,10 @@
 #include <stdint.h>
 #include <stdio.h>
+#include <stdlib.h>
 
 #include "ram.h"
 
-ram_ctx_t ram_ctx = {0, 0, 0};
+ram_ctx_t ram_ctx = {0};
 
 int
 read_ram_usage(void)
@@ -18,24 +19,31 @@ read_ram_usage(void)
   size_t len = 0;
   char *line = NULL;
 
+  /* One pass, the keys are not all adjacent in /proc/meminfo */
   while ((getline(&line, &len, status_fd)) != -1) {
-    if (sscanf(line, "MemTotal: %u kB\n", &(ram_ctx.mem_total)) == 1) {
-      break;
-    }
-  }
-
-  while ((getline(&line, &len, status_fd)) != -1) {
-    if (sscanf(line, "MemFree: %u kB\n", &(ram_ctx.mem_free)) == 1) {
-      break;
-    }
-  }
-
-  while ((getline(&line, &len, status_fd)) != -1) {
-    if (sscanf(line, "MemAvailable: %u kB\n", &(ram_ctx.mem_available)) == 1) {
-      break;
-    }
+    if (sscanf(line, "MemTotal: %u kB\n", &(ram_ctx.mem_total)) == 1)
+      continue;
+    if (sscanf(line, "MemFree: %u kB\n", &(ram_ctx.mem_free)) == 1)
+      continue;
+    if (sscanf(line, "MemAvailable: %u kB\n", &(ram_ctx.mem_available)) == 1)
+      continue;
+    if (sscanf(line, "Buffers: %u kB\n", &(ram_ctx.buffers)) == 1)
+      continue;
+    if (sscanf(line, "Cached: %u kB\n", &(ram_ctx.cached)) == 1)
+      continue;
+    if (sscanf(line, "SwapTotal: %u kB\n", &(ram_ctx.swap_total)) == 1)
+      continue;
+    if (sscanf(line, "SwapFree: %u kB\n", &(ram_ctx.swap_free)) == 1)
+      continue;
+    if (sscanf(line, "Dirty: %u kB\n", &(ram_ctx.dirty)) == 1)
+      continue;
+    if (sscanf(line, "Shmem: %u kB\n", &(ram_ctx.shmem)) == 1)
+      continue;
+    if (sscanf(line, "Slab: %u kB\n", &(ram_ctx.slab)) == 1)
+      continue;
   }
 
+  free(line);
   fclose(status_fd);
 
   return 0;
@@ -46,4 +54,11 @@ void print_ram_usage(FILE *fd)
   fprintf(fd, "Ram total: %u kB\n", ram_ctx.mem_total);
   fprintf(fd, "Ram free: %u\ kB\n", ram_ctx.mem_free);
   fprintf(fd, "Ram available: %u kB\n", ram_ctx.mem_available);
+  fprintf(fd, "Buffers: %u kB\n", ram_ctx.buffers);
+  fprintf(fd, "Cached: %u kB\n", ram_ctx.cached);
+  fprintf(fd, "Shared: %u kB\n", ram_ctx.shmem);
+  fprintf(fd, "Dirty: %u kB\n", ram_ctx.dirty);
+  fprintf(fd, "Slab: %u kB\n", ram_ctx.slab);
+  fprintf(fd, "Swap total: %u kB\n", ram_ctx.swap_total);
+  fprintf(fd, "Swap free: %u kB\n", ram_ctx.swap_free);
 }
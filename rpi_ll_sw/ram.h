@@ -8,6 +8,13 @@ typedef struct{
   uint32_t mem_total;
   uint32_t mem_free;
   uint32_t mem_available;
+  uint32_t buffers;
+  uint32_t cached;
+  uint32_t swap_total;
+  uint32_t swap_free;
+  uint32_t dirty;
+  uint32_t shmem;
+  uint32_t slab;
 } ram_ctx_t;
 
 int read_ram_usage(void);
@@ -0,0 +1,46 @@
+#include <stdint.h>
+#include <stdio.h>
+
+#include "system.h"
+
+system_ctx_t system_ctx = {0};
+
+int
+read_system_info(void)
+{
+  FILE *loadavg_fd = fopen(LOADAVG_PATH, "r");
+
+  if (loadavg_fd == NULL) {
+    perror("fopen");
+    return -1;
+  }
+
+  /* "0.15 0.10 0.05 1/123 4567" */
+  if (fscanf(loadavg_fd, "%f %f %f %u/%u",
+             &(system_ctx.load1), &(system_ctx.load5), &(system_ctx.load15),
+             &(system_ctx.running_tasks), &(system_ctx.total_tasks)) != 5) {
+    fprintf(stderr, "Unexpected format of %s\n", LOADAVG_PATH);
+  }
+  fclose(loadavg_fd);
+
+  FILE *uptime_fd = fopen(UPTIME_PATH, "r");
+
+  if (uptime_fd == NULL) {
+    perror("fopen");
+    return -1;
+  }
+
+  if (fscanf(uptime_fd, "%lf", &(system_ctx.uptime_s)) != 1) {
+    fprintf(stderr, "Unexpected format of %s\n", UPTIME_PATH);
+  }
+  fclose(uptime_fd);
+
+  return 0;
+}
+
+void print_system_info(FILE *fd)
+{
+  fprintf(fd, "Load: %.2f %.2f %.2f\n", system_ctx.load1, system_ctx.load5, system_ctx.load15);
+  fprintf(fd, "Tasks: %u/%u\n", system_ctx.running_tasks, system_ctx.total_tasks);
+  fprintf(fd, "Uptime: %.2f\n", system_ctx.uptime_s);
+}
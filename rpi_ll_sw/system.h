@@ -0,0 +1,19 @@
+#pragma once
+
+#include <stdint.h>
+#include <stdio.h>
+
+#define LOADAVG_PATH "/proc/loadavg"
+#define UPTIME_PATH "/proc/uptime"
+
+typedef struct{
+  float load1;
+  float load5;
+  float load15;
+  uint32_t running_tasks;
+  uint32_t total_tasks;
+  double uptime_s;
+} system_ctx_t;
+
+int read_system_info(void);
+void print_system_info(FILE *fd);
@@ -0,0 +1,12 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Milliseconds since the Unix epoch, the timestamp unit used in API responses.
+pub fn to_unix_ms(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+pub fn unix_now_ms() -> u64 {
+    to_unix_ms(SystemTime::now())
+}
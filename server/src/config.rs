@@ -1,17 +1,210 @@
-use std::{env, net::SocketAddr, path::PathBuf, str::FromStr};
+use crate::models::TemperatureUnit;
+use std::{env, fmt, net::SocketAddr, path::PathBuf, str::FromStr};
 use tracing::warn;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataSourceKind {
+    // Text files written by the rpi_watch daemon
+    StatusFiles,
+    // Built-in collector reading /proc and /sys directly
+    Procfs,
+}
+
+impl FromStr for DataSourceKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "files" | "status_files" => Ok(DataSourceKind::StatusFiles),
+            "procfs" | "native" => Ok(DataSourceKind::Procfs),
+            other => Err(format!("unknown data source '{}'", other)),
+        }
+    }
+}
+
+impl fmt::Display for DataSourceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DataSourceKind::StatusFiles => write!(f, "files"),
+            DataSourceKind::Procfs => write!(f, "procfs"),
+        }
+    }
+}
+
+// What to serve for a section whose source could not be read or parsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorPolicy {
+    // Keep the last good data, marked stale
+    Retain,
+    // Serve the section empty until the next successful read
+    Clear,
+}
+
+impl FromStr for ParseErrorPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "retain" => Ok(ParseErrorPolicy::Retain),
+            "clear" => Ok(ParseErrorPolicy::Clear),
+            other => Err(format!("unknown parse error policy '{}'", other)),
+        }
+    }
+}
+
+impl fmt::Display for ParseErrorPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseErrorPolicy::Retain => write!(f, "retain"),
+            ParseErrorPolicy::Clear => write!(f, "clear"),
+        }
+    }
+}
+
+// What triggers a refresh of the status data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefreshMode {
+    // Re-read everything every update interval
+    Poll,
+    // Re-read a status file as soon as it has been written
+    Watch,
+}
+
+impl FromStr for RefreshMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "poll" => Ok(RefreshMode::Poll),
+            "watch" | "inotify" => Ok(RefreshMode::Watch),
+            other => Err(format!("unknown refresh mode '{}'", other)),
+        }
+    }
+}
+
+impl fmt::Display for RefreshMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RefreshMode::Poll => write!(f, "poll"),
+            RefreshMode::Watch => write!(f, "watch"),
+        }
+    }
+}
+
+// An external temperature sensor and the status file its value is written to
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtSensorConfig {
+    pub name: String,
+    pub file: PathBuf,
+    pub unit: TemperatureUnit,
+}
+
+// Parsed from "name[:unit]=path" entries separated by commas, e.g.
+// "enclosure=/tmp/ext_temp,intake:F=/tmp/ext_temp_intake"
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtSensorList(pub Vec<ExtSensorConfig>);
+
+impl FromStr for ExtSensorList {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut sensors: Vec<ExtSensorConfig> = Vec::new();
+        for entry in s.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (label, file) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("sensor '{}' has no file", entry))?;
+            let (name, unit) = match label.split_once(':') {
+                Some((name, unit)) => (name.trim(), unit.parse()?),
+                None => (label.trim(), TemperatureUnit::Celsius),
+            };
+            // The name is used as a path segment of /ext_temp/{name}
+            if name.is_empty()
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            {
+                return Err(format!("invalid sensor name '{}'", name));
+            }
+            if sensors.iter().any(|sensor| sensor.name == name) {
+                return Err(format!("duplicate sensor name '{}'", name));
+            }
+            sensors.push(ExtSensorConfig {
+                name: name.to_string(),
+                file: PathBuf::from(file.trim()),
+                unit,
+            });
+        }
+        if sensors.is_empty() {
+            return Err("no sensors given".to_string());
+        }
+        Ok(ExtSensorList(sensors))
+    }
+}
+
+impl fmt::Display for ExtSensorList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, sensor) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(
+                f,
+                "{}:{}={}",
+                sensor.name,
+                sensor.unit,
+                sensor.file.display()
+            )?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Settings {
     // Server Settings
     pub bind_address: SocketAddr,
     pub update_interval_secs: u64,
+    pub refresh_mode: RefreshMode,
+    // Quiet time after a file change before re-reading, so that files written
+    // together are read together
+    pub watch_debounce_ms: u64,
+    // Full re-read in watch mode, in case a change notification was missed
+    pub watch_fallback_secs: u64,
+    // Data whose source file is older than this is reported as stale
+    pub max_data_age_secs: u64,
+    pub parse_error_policy: ParseErrorPolicy,
+    // Keep line-level parse findings for GET /diagnostics/parse
+    pub strict_parsing: bool,
     pub log_level: String,
+    // Time series kept for GET /history
+    pub history_retention_secs: u64,
+    pub history_max_samples: usize,
+    // Persistent metric store; disabled when DATA_DIR is empty
+    pub data_dir: Option<PathBuf>,
+    pub store_flush_secs: u64,
+    pub store_raw_retention_secs: u64,
+    pub store_minute_retention_secs: u64,
+    pub store_hour_retention_secs: u64,
+    // Processes recorded per refresh, by CPU and by memory
+    pub store_top_processes: usize,
+    // Processes with their own series on GET /metrics
+    pub metrics_max_processes: usize,
+    // Shortest time between two snapshots sent to a /status/ws client
+    pub ws_min_interval_ms: u64,
+    // Data Source Selection
+    pub data_source: DataSourceKind,
+    pub procfs_root: PathBuf,
+    pub sysfs_root: PathBuf,
     // Data Source Files
     pub cpu_file: PathBuf,
     pub ram_file: PathBuf,
     pub proc_file: PathBuf,
-    pub ext_temp_file: PathBuf,
+    // EXT_SENSORS, or a single sensor "external" read from EXT_TEMP_FILE
+    pub ext_sensors: Vec<ExtSensorConfig>,
+    pub system_file: PathBuf,
+    // Reads of a status file caught mid-write before giving up
+    pub read_attempts: u32,
+    pub read_retry_backoff_ms: u64,
     // Controller Settings
     pub controller_host: String,
     pub controller_port: u16,
@@ -71,16 +264,53 @@ impl Settings {
                     .expect("Default BIND_ADDRESS must parse"),
             ),
             update_interval_secs: get_env_var("UPDATE_INTERVAL_SECS", 5u64),
+            refresh_mode: get_env_var("REFRESH_MODE", RefreshMode::Poll),
+            watch_debounce_ms: get_env_var("WATCH_DEBOUNCE_MS", 100u64),
+            watch_fallback_secs: get_env_var("WATCH_FALLBACK_SECS", 60u64),
+            max_data_age_secs: get_env_var("MAX_DATA_AGE_SECS", 30u64),
+            parse_error_policy: get_env_var("PARSE_ERROR_POLICY", ParseErrorPolicy::Retain),
+            strict_parsing: get_env_var("STRICT_PARSING", false),
             log_level: get_env_var_string("LOG_LEVEL", "info".to_string()),
+            history_retention_secs: get_env_var("HISTORY_RETENTION_SECS", 3600u64),
+            history_max_samples: get_env_var("HISTORY_MAX_SAMPLES", 10_000usize),
+            data_dir: Some(get_env_var_string("DATA_DIR", String::new()))
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from),
+            store_flush_secs: get_env_var("STORE_FLUSH_SECS", 60u64),
+            store_raw_retention_secs: get_env_var("STORE_RAW_RETENTION_SECS", 86_400u64),
+            store_minute_retention_secs: get_env_var("STORE_MINUTE_RETENTION_SECS", 604_800u64),
+            store_hour_retention_secs: get_env_var("STORE_HOUR_RETENTION_SECS", 31_536_000u64),
+            store_top_processes: get_env_var("STORE_TOP_PROCESSES", 10usize),
+            metrics_max_processes: get_env_var("METRICS_MAX_PROCESSES", 20usize),
+            ws_min_interval_ms: get_env_var("WS_MIN_INTERVAL_MS", 1000u64),
+
+            // --- Data Source Selection ---
+            data_source: get_env_var("DATA_SOURCE", DataSourceKind::StatusFiles),
+            procfs_root: PathBuf::from(get_env_var_string("PROCFS_ROOT", "/proc".to_string())),
+            sysfs_root: PathBuf::from(get_env_var_string("SYSFS_ROOT", "/sys".to_string())),
 
             // --- Data Source Files ---
             cpu_file: PathBuf::from(get_env_var_string("CPU_FILE", "/tmp/cpu".to_string())),
             ram_file: PathBuf::from(get_env_var_string("RAM_FILE", "/tmp/ram".to_string())),
             proc_file: PathBuf::from(get_env_var_string("PROC_FILE", "/tmp/proc".to_string())),
-            ext_temp_file: PathBuf::from(get_env_var_string(
-                "EXT_TEMP_FILE",
-                "/tmp/ext_temp".to_string(),
+            ext_sensors: get_env_var(
+                "EXT_SENSORS",
+                ExtSensorList(vec![ExtSensorConfig {
+                    name: "external".to_string(),
+                    file: PathBuf::from(get_env_var_string(
+                        "EXT_TEMP_FILE",
+                        "/tmp/ext_temp".to_string(),
+                    )),
+                    unit: TemperatureUnit::Celsius,
+                }]),
+            )
+            .0,
+            system_file: PathBuf::from(get_env_var_string(
+                "SYSTEM_FILE",
+                "/tmp/system".to_string(),
             )),
+            read_attempts: get_env_var("READ_ATTEMPTS", 4u32),
+            read_retry_backoff_ms: get_env_var("READ_RETRY_BACKOFF_MS", 10u64),
 
             // --- Controller Settings ---
             controller_host: get_env_var_string("CONTROL_HOST", "127.0.0.1".to_string()),
@@ -1,6 +1,8 @@
+use crate::clock::unix_now_ms;
 use byteorder::{LittleEndian, WriteBytesExt};
 use std::io::{self, Cursor};
 use std::net::{AddrParseError, SocketAddr};
+use std::sync::Arc;
 use std::time::Duration;
 use thiserror::Error;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
@@ -11,6 +13,9 @@ use tracing::{debug, error, info, warn};
 
 const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
 const CMD_TIMEOUT: Duration = Duration::from_secs(5);
+// The controller is pinged when it has not been contacted for this long, so
+// `last_contact` stays current without a ping per /metrics scrape
+const PROBE_INTERVAL: Duration = Duration::from_secs(30);
 
 const CMD_KILL_PROCESS: u8 = 0x00;
 const CMD_GPIO_SET: u8 = 0x01;
@@ -18,11 +23,9 @@ const CMD_PING: u8 = 0x02;
 const CMD_SHUTDOWN: u8 = 0x03;
 const CMD_REBOOT: u8 = 0x04;
 
+// Any other response is an error: 0x01 generic, 0x02 invalid command,
+// 0x03 invalid argument, 0x04 permission denied
 pub const RESP_OK: u8 = 0x00;
-pub const RESP_ERROR_GENERIC: u8 = 0x01;
-pub const RESP_ERROR_INVALID_CMD: u8 = 0x02;
-pub const RESP_ERROR_INVALID_ARG: u8 = 0x03;
-pub const RESP_ERROR_PERMISSION: u8 = 0x04;
 
 // --- Error Enum ---
 #[derive(Error, Debug)]
@@ -72,12 +75,21 @@ impl CommandPacket {
     }
 }
 
+// Outcome of the most recent exchange with the controller
+#[derive(Debug, Clone, Copy)]
+pub struct ControllerContact {
+    // An error code from the controller still counts as reachable
+    pub reachable: bool,
+    pub at: u64, // Unix epoch milliseconds
+}
+
 // --- Controller Client Struct ---
 #[derive(Debug)]
 pub struct ControllerClient {
     stream: Mutex<Option<TcpStream>>,
     addr: SocketAddr,
     key: u32,
+    last_contact: std::sync::Mutex<Option<ControllerContact>>,
 }
 
 impl ControllerClient {
@@ -96,9 +108,21 @@ impl ControllerClient {
             stream: Mutex::new(None),
             addr,
             key,
+            last_contact: std::sync::Mutex::new(None),
         })
     }
 
+    pub fn last_contact(&self) -> Option<ControllerContact> {
+        *self.last_contact.lock().expect("contact lock poisoned")
+    }
+
+    fn record_contact(&self, reachable: bool) {
+        *self.last_contact.lock().expect("contact lock poisoned") = Some(ControllerContact {
+            reachable,
+            at: unix_now_ms(),
+        });
+    }
+
     async fn get_connection(
         &self,
     ) -> Result<tokio::sync::MutexGuard<'_, Option<TcpStream>>, ControlError> {
@@ -172,7 +196,13 @@ impl ControllerClient {
             command_id, arg1, packet_bytes
         );
 
-        let mut stream_guard = self.get_connection().await?;
+        let mut stream_guard = match self.get_connection().await {
+            Ok(stream_guard) => stream_guard,
+            Err(e) => {
+                self.record_contact(false);
+                return Err(e);
+            }
+        };
         let stream_opt = stream_guard.as_mut();
 
         let stream = match stream_opt {
@@ -194,6 +224,7 @@ impl ControllerClient {
         }
         .await;
 
+        self.record_contact(result.is_ok());
         match result {
             Ok(response_code) => {
                 debug!("Received response code: 0x{:02X}", response_code);
@@ -251,6 +282,24 @@ impl ControllerClient {
     }
 }
 
+// Background task keeping `last_contact` current for GET /metrics, so a
+// scrape never waits on the controller.
+pub async fn run_controller_probe(client: Arc<ControllerClient>) {
+    let mut ticker = tokio::time::interval(PROBE_INTERVAL);
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        let probe_after = unix_now_ms().saturating_sub(PROBE_INTERVAL.as_millis() as u64);
+        let stale = client
+            .last_contact()
+            .is_none_or(|contact| contact.at < probe_after);
+        if stale {
+            // The outcome is recorded by the client
+            let _ = client.ping_controller().await;
+        }
+    }
+}
+
 impl From<tokio::time::error::Elapsed> for ControlError {
     fn from(_: tokio::time::error::Elapsed) -> Self {
         ControlError::Timeout
@@ -1,5 +1,18 @@
+use crate::clock::to_unix_ms;
+use crate::config::{ExtSensorConfig, ExtSensorList};
+use crate::disks::collect_disks;
 use crate::models::*;
-use std::{io, path::Path};
+use crate::network::collect_network;
+use crate::procfs::read_process_identity;
+use crate::source::{Collection, CounterKind, ProcessIdentity, SourceCapabilities, StatusSource};
+use crate::status_json::{self, JsonSection};
+use crate::thermal::collect_thermal;
+use futures_util::future::BoxFuture;
+use std::{
+    io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 use thiserror::Error;
 use tokio::fs;
 use tracing::{debug, error, warn};
@@ -21,12 +34,26 @@ pub enum ParseError {
         line: String,
         value: String,
     },
+    #[error("File '{file}' was read while being written: {message}")]
+    Torn { file: String, message: String },
+}
+
+impl ParseError {
+    // Path of the file the error refers to
+    pub fn file(&self) -> &str {
+        match self {
+            ParseError::Io(file, _) => file,
+            ParseError::FileFormat { file, .. } => file,
+            ParseError::ValueFormat { file, .. } => file,
+            ParseError::Torn { file, .. } => file,
+        }
+    }
 }
 
 fn extract_value<'a>(line: &'a str, key_prefix: &str) -> Option<&'a str> {
     line.strip_prefix(key_prefix)
         .and_then(|s| s.split(':').nth(1))
-        .map(|s| s.trim().split_whitespace().next().unwrap_or(""))
+        .map(|s| s.split_whitespace().next().unwrap_or(""))
         .filter(|s| !s.is_empty())
 }
 
@@ -43,13 +70,87 @@ fn parse_numeric<T: std::str::FromStr>(
     })
 }
 
-// --- File Specific Parsers ---
+// Per-file parsing state. Lines the parser cannot use are logged and, in
+// strict mode, kept as findings for GET /diagnostics/parse.
+pub struct ParseContext<'a> {
+    file_path: &'a Path,
+    strict: bool,
+    // Lines before the parsed content, e.g. a "Seq:" header
+    line_offset: usize,
+    line_number: usize,
+    findings: Vec<LineFinding>,
+}
+
+impl<'a> ParseContext<'a> {
+    fn new(file_path: &'a Path, strict: bool, line_offset: usize) -> Self {
+        ParseContext {
+            file_path,
+            strict,
+            line_offset,
+            line_number: line_offset,
+            findings: Vec::new(),
+        }
+    }
+
+    fn next_line(&mut self, index: usize) {
+        self.line_number = self.line_offset + index + 1;
+    }
+
+    fn record(&mut self, line: &str, reason: String) {
+        if self.strict {
+            self.findings.push(LineFinding {
+                line_number: self.line_number,
+                line: line.to_string(),
+                reason,
+            });
+        }
+    }
+
+    fn warn_line(&mut self, line: &str, reason: &str) {
+        warn!(
+            "{}:{}: {}: '{}'",
+            self.file_path.display(),
+            self.line_number,
+            reason,
+            line
+        );
+        self.record(line, reason.to_string());
+    }
+
+    fn debug_line(&mut self, line: &str, reason: &str) {
+        debug!(
+            "{}:{}: {}: '{}'",
+            self.file_path.display(),
+            self.line_number,
+            reason,
+            line
+        );
+        self.record(line, reason.to_string());
+    }
+
+    fn value_error(&mut self, error: ParseError) {
+        warn!(
+            "{}:{}: {}",
+            self.file_path.display(),
+            self.line_number,
+            error
+        );
+        if let ParseError::ValueFormat {
+            message,
+            line,
+            value,
+            ..
+        } = error
+        {
+            self.record(&line, format!("{} (value '{}')", message, value));
+        }
+    }
+}
 
-async fn parse_cpu_file(file_path: &Path) -> Result<CpuInfo, ParseError> {
-    let content = fs::read_to_string(file_path)
-        .await
-        .map_err(|e| ParseError::Io(file_path.to_string_lossy().into_owned(), e))?;
+// --- File Specific Parsers ---
 
+fn parse_cpu_file(content: &str, ctx: &mut ParseContext) -> Result<CpuInfo, ParseError> {
+    let file_path = ctx.file_path;
     let mut cpu_info = CpuInfo::default();
     // Initialize usage structure here as it's part of cpu_info
     let mut cpu_usage = CpuUsage::default();
@@ -57,7 +158,8 @@ async fn parse_cpu_file(file_path: &Path) -> Result<CpuInfo, ParseError> {
     let mut cores = Vec::new();
     let mut parsing_full_cpu = false;
 
-    for line in content.lines() {
+    for (index, line) in content.lines().enumerate() {
+        ctx.next_line(index);
         let trimmed_line = line.trim();
         if trimmed_line.is_empty() {
             continue;
@@ -67,7 +169,7 @@ async fn parse_cpu_file(file_path: &Path) -> Result<CpuInfo, ParseError> {
         if let Some(value_str) = extract_value(trimmed_line, "CPU temp") {
             match parse_numeric::<f32>(value_str, trimmed_line, file_path) {
                 Ok(temp_milli) => cpu_info.cpu_temperature = Some(temp_milli / 1000.0),
-                Err(e) => warn!("{}: {}", file_path.display(), e),
+                Err(e) => ctx.value_error(e),
             }
             parsing_full_cpu = false;
             if current_core_stat.is_some() {
@@ -106,18 +208,10 @@ async fn parse_cpu_file(file_path: &Path) -> Result<CpuInfo, ParseError> {
                             stats: CpuStat::default(),
                         })
                     }
-                    Err(_) => warn!(
-                        "{}: Failed to parse core ID from '{}'",
-                        file_path.display(),
-                        trimmed_line
-                    ),
+                    Err(_) => ctx.warn_line(trimmed_line, "Failed to parse core ID"),
                 }
             } else {
-                warn!(
-                    "{}: Malformed Core header line: '{}'",
-                    file_path.display(),
-                    trimmed_line
-                );
+                ctx.warn_line(trimmed_line, "Malformed Core header line");
             }
         } else {
             // Try parsing as Full CPU Stat if in that section
@@ -126,45 +220,41 @@ async fn parse_cpu_file(file_path: &Path) -> Result<CpuInfo, ParseError> {
                     if let Some(val_str) = extract_value(trimmed_line, "User norm") {
                         match parse_numeric::<u64>(val_str, trimmed_line, file_path) {
                             Ok(val) => stat.user_norm = Some(val),
-                            Err(e) => warn!("{}: {}", file_path.display(), e),
+                            Err(e) => ctx.value_error(e),
                         }
                     } else if let Some(val_str) = extract_value(trimmed_line, "User nice") {
                         match parse_numeric::<u64>(val_str, trimmed_line, file_path) {
                             Ok(val) => stat.user_nice = Some(val),
-                            Err(e) => warn!("{}: {}", file_path.display(), e),
+                            Err(e) => ctx.value_error(e),
                         }
                     } else if let Some(val_str) = extract_value(trimmed_line, "Kernel") {
                         match parse_numeric::<u64>(val_str, trimmed_line, file_path) {
                             Ok(val) => stat.kernel = Some(val),
-                            Err(e) => warn!("{}: {}", file_path.display(), e),
+                            Err(e) => ctx.value_error(e),
                         }
                     } else if let Some(val_str) = extract_value(trimmed_line, "Idle") {
                         match parse_numeric::<u64>(val_str, trimmed_line, file_path) {
                             Ok(val) => stat.idle = Some(val),
-                            Err(e) => warn!("{}: {}", file_path.display(), e),
+                            Err(e) => ctx.value_error(e),
                         }
                     } else if let Some(val_str) = extract_value(trimmed_line, "Iowait") {
                         match parse_numeric::<u64>(val_str, trimmed_line, file_path) {
                             Ok(val) => stat.iowait = Some(val),
-                            Err(e) => warn!("{}: {}", file_path.display(), e),
+                            Err(e) => ctx.value_error(e),
                         }
                     } else if let Some(val_str) = extract_value(trimmed_line, "Irq") {
                         match parse_numeric::<u64>(val_str, trimmed_line, file_path) {
                             Ok(val) => stat.irq = Some(val),
-                            Err(e) => warn!("{}: {}", file_path.display(), e),
+                            Err(e) => ctx.value_error(e),
                         }
                     } else if let Some(val_str) = extract_value(trimmed_line, "Soft irq") {
                         match parse_numeric::<u64>(val_str, trimmed_line, file_path) {
                             Ok(val) => stat.soft_irq = Some(val),
-                            Err(e) => warn!("{}: {}", file_path.display(), e),
+                            Err(e) => ctx.value_error(e),
                         }
                     } else {
                         // Line is within "Full CPU" but doesn't match known keys
-                        warn!(
-                            "{}: Ignoring unrecognized line in Full CPU section: {}",
-                            file_path.display(),
-                            trimmed_line
-                        );
+                        ctx.warn_line(trimmed_line, "Unrecognized line in Full CPU section");
                     }
                 }
             }
@@ -173,55 +263,47 @@ async fn parse_cpu_file(file_path: &Path) -> Result<CpuInfo, ParseError> {
                 if let Some(val_str) = extract_value(trimmed_line, "User norm") {
                     match parse_numeric::<u64>(val_str, trimmed_line, file_path) {
                         Ok(val) => core.stats.user_norm = Some(val),
-                        Err(e) => warn!("{}: {}", file_path.display(), e),
+                        Err(e) => ctx.value_error(e),
                     }
                 } else if let Some(val_str) = extract_value(trimmed_line, "User nice") {
                     match parse_numeric::<u64>(val_str, trimmed_line, file_path) {
                         Ok(val) => core.stats.user_nice = Some(val),
-                        Err(e) => warn!("{}: {}", file_path.display(), e),
+                        Err(e) => ctx.value_error(e),
                     }
                 } else if let Some(val_str) = extract_value(trimmed_line, "Kernel") {
                     match parse_numeric::<u64>(val_str, trimmed_line, file_path) {
                         Ok(val) => core.stats.kernel = Some(val),
-                        Err(e) => warn!("{}: {}", file_path.display(), e),
+                        Err(e) => ctx.value_error(e),
                     }
                 } else if let Some(val_str) = extract_value(trimmed_line, "Idle") {
                     match parse_numeric::<u64>(val_str, trimmed_line, file_path) {
                         Ok(val) => core.stats.idle = Some(val),
-                        Err(e) => warn!("{}: {}", file_path.display(), e),
+                        Err(e) => ctx.value_error(e),
                     }
                 } else if let Some(val_str) = extract_value(trimmed_line, "Iowait") {
                     match parse_numeric::<u64>(val_str, trimmed_line, file_path) {
                         Ok(val) => core.stats.iowait = Some(val),
-                        Err(e) => warn!("{}: {}", file_path.display(), e),
+                        Err(e) => ctx.value_error(e),
                     }
                 } else if let Some(val_str) = extract_value(trimmed_line, "Irq") {
                     match parse_numeric::<u64>(val_str, trimmed_line, file_path) {
                         Ok(val) => core.stats.irq = Some(val),
-                        Err(e) => warn!("{}: {}", file_path.display(), e),
+                        Err(e) => ctx.value_error(e),
                     }
                 } else if let Some(val_str) = extract_value(trimmed_line, "Soft irq") {
                     match parse_numeric::<u64>(val_str, trimmed_line, file_path) {
                         Ok(val) => core.stats.soft_irq = Some(val),
-                        Err(e) => warn!("{}: {}", file_path.display(), e),
+                        Err(e) => ctx.value_error(e),
                     }
                 } else {
                     // Line is within a "Core" but doesn't match known keys
-                    warn!(
-                        "{}: Ignoring unrecognized line in Core {} section: {}",
-                        file_path.display(),
-                        core.core_id,
-                        trimmed_line
-                    );
+                    let reason = format!("Unrecognized line in Core {} section", core.core_id);
+                    ctx.warn_line(trimmed_line, &reason);
                 }
             }
             // Otherwise, it's an unrecognized line outside known sections
             else {
-                warn!(
-                    "{}: Ignoring unrecognized line: {}",
-                    file_path.display(),
-                    trimmed_line
-                );
+                ctx.warn_line(trimmed_line, "Unrecognized line");
             }
         }
     }
@@ -230,6 +312,13 @@ async fn parse_cpu_file(file_path: &Path) -> Result<CpuInfo, ParseError> {
     if let Some(core) = current_core_stat.take() {
         cores.push(core);
     }
+    // A header without values is what a file cut off mid-write looks like
+    if let Some(core) = cores.iter().find(|core| core.stats.idle.is_none()) {
+        return Err(ParseError::Torn {
+            file: file_path.to_string_lossy().into_owned(),
+            message: format!("Core {} has no values", core.core_id),
+        });
+    }
     if !cores.is_empty() {
         cpu_usage.cores = Some(cores);
     }
@@ -243,13 +332,12 @@ async fn parse_cpu_file(file_path: &Path) -> Result<CpuInfo, ParseError> {
 }
 
 // Parses /tmp/ram
-async fn parse_ram_file(file_path: &Path) -> Result<MemoryInfo, ParseError> {
-    let content = fs::read_to_string(file_path)
-        .await
-        .map_err(|e| ParseError::Io(file_path.to_string_lossy().into_owned(), e))?;
+fn parse_ram_file(content: &str, ctx: &mut ParseContext) -> Result<MemoryInfo, ParseError> {
+    let file_path = ctx.file_path;
     let mut memory_info = MemoryInfo::default();
 
-    for line in content.lines() {
+    for (index, line) in content.lines().enumerate() {
+        ctx.next_line(index);
         let trimmed_line = line.trim();
         if trimmed_line.is_empty() {
             continue;
@@ -258,39 +346,69 @@ async fn parse_ram_file(file_path: &Path) -> Result<MemoryInfo, ParseError> {
         if let Some(val_str) = extract_value(trimmed_line, "Ram total") {
             match parse_numeric::<u64>(val_str, trimmed_line, file_path) {
                 Ok(val) => memory_info.total = Some(val),
-                Err(e) => warn!("{}: {}", file_path.display(), e),
+                Err(e) => ctx.value_error(e),
             }
         } else if let Some(val_str) = extract_value(trimmed_line, "Ram free") {
             match parse_numeric::<u64>(val_str, trimmed_line, file_path) {
                 Ok(val) => memory_info.free = Some(val),
-                Err(e) => warn!("{}: {}", file_path.display(), e),
+                Err(e) => ctx.value_error(e),
             }
         } else if let Some(val_str) = extract_value(trimmed_line, "Ram available") {
             match parse_numeric::<u64>(val_str, trimmed_line, file_path) {
                 Ok(val) => memory_info.available = Some(val),
-                Err(e) => warn!("{}: {}", file_path.display(), e),
+                Err(e) => ctx.value_error(e),
+            }
+        } else if let Some(val_str) = extract_value(trimmed_line, "Buffers") {
+            match parse_numeric::<u64>(val_str, trimmed_line, file_path) {
+                Ok(val) => memory_info.buffers = Some(val),
+                Err(e) => ctx.value_error(e),
+            }
+        } else if let Some(val_str) = extract_value(trimmed_line, "Cached") {
+            match parse_numeric::<u64>(val_str, trimmed_line, file_path) {
+                Ok(val) => memory_info.cached = Some(val),
+                Err(e) => ctx.value_error(e),
+            }
+        } else if let Some(val_str) = extract_value(trimmed_line, "Shared") {
+            match parse_numeric::<u64>(val_str, trimmed_line, file_path) {
+                Ok(val) => memory_info.shared = Some(val),
+                Err(e) => ctx.value_error(e),
+            }
+        } else if let Some(val_str) = extract_value(trimmed_line, "Dirty") {
+            match parse_numeric::<u64>(val_str, trimmed_line, file_path) {
+                Ok(val) => memory_info.dirty = Some(val),
+                Err(e) => ctx.value_error(e),
+            }
+        } else if let Some(val_str) = extract_value(trimmed_line, "Slab") {
+            match parse_numeric::<u64>(val_str, trimmed_line, file_path) {
+                Ok(val) => memory_info.slab = Some(val),
+                Err(e) => ctx.value_error(e),
+            }
+        } else if let Some(val_str) = extract_value(trimmed_line, "Swap total") {
+            match parse_numeric::<u64>(val_str, trimmed_line, file_path) {
+                Ok(val) => memory_info.swap_total = Some(val),
+                Err(e) => ctx.value_error(e),
+            }
+        } else if let Some(val_str) = extract_value(trimmed_line, "Swap free") {
+            match parse_numeric::<u64>(val_str, trimmed_line, file_path) {
+                Ok(val) => memory_info.swap_free = Some(val),
+                Err(e) => ctx.value_error(e),
             }
         } else {
-            // Ignore other lines silently
-            debug!(
-                "{}: Ignoring unrecognized line: {}",
-                file_path.display(),
-                trimmed_line
-            );
+            // Ignore other lines quietly
+            ctx.debug_line(trimmed_line, "Unrecognized line");
         }
     }
     Ok(memory_info)
 }
 
 // Parses /tmp/proc (Process Info)
-async fn parse_proc_file(file_path: &Path) -> Result<ProcessesInfo, ParseError> {
-    let content = fs::read_to_string(file_path)
-        .await
-        .map_err(|e| ParseError::Io(file_path.to_string_lossy().into_owned(), e))?;
+fn parse_proc_file(content: &str, ctx: &mut ParseContext) -> Result<ProcessesInfo, ParseError> {
+    let file_path = ctx.file_path;
     let mut processes_info = ProcessesInfo::default();
     let mut current_process: Option<ProcessInfo> = None;
 
-    for line in content.lines() {
+    for (index, line) in content.lines().enumerate() {
+        ctx.next_line(index);
         let trimmed_line = line.trim();
         if trimmed_line.is_empty() {
             continue;
@@ -319,25 +437,17 @@ async fn parse_proc_file(file_path: &Path) -> Result<ProcessesInfo, ParseError>
                         });
                     }
                     Err(_) => {
-                        warn!(
-                            "{}: Failed to parse PID from Proc line: '{}'",
-                            file_path.display(),
-                            trimmed_line
-                        );
+                        ctx.warn_line(trimmed_line, "Failed to parse PID from Proc line");
                         current_process = None;
                     }
                 }
             } else {
-                warn!(
-                    "{}: Malformed Proc line: '{}'",
-                    file_path.display(),
-                    trimmed_line
-                );
+                ctx.warn_line(trimmed_line, "Malformed Proc line");
                 current_process = None;
             }
         } else if let Some(proc) = current_process.as_mut() {
             if trimmed_line.starts_with("State:") {
-                if let Some(value_part) = trimmed_line.splitn(2, ':').nth(1) {
+                if let Some((_, value_part)) = trimmed_line.split_once(':') {
                     let value_trimmed = value_part.trim(); // e.g., "S (sleeping)" or "R"
                     if let Some((code, desc_part)) = value_trimmed.split_once('(') {
                         // Case: "S (sleeping)"
@@ -352,11 +462,12 @@ async fn parse_proc_file(file_path: &Path) -> Result<ProcessesInfo, ParseError>
                     }
                 } else {
                     // Should not happen if starts_with("State:") is true, but defensive check
-                    warn!(
-                        "{}: Malformed State line (missing ':'?): '{}'",
-                        file_path.display(),
-                        trimmed_line
-                    );
+                    ctx.warn_line(trimmed_line, "Malformed State line (missing ':'?)");
+                }
+            } else if let Some(value_str) = extract_value(trimmed_line, "PPid") {
+                match parse_numeric::<u32>(value_str, trimmed_line, file_path) {
+                    Ok(val) => proc.ppid = Some(val),
+                    Err(e) => ctx.value_error(e),
                 }
             } else if let Some(value_str) = extract_value(trimmed_line, "User") {
                 proc.user = value_str.split_whitespace().last().map(String::from);
@@ -367,34 +478,35 @@ async fn parse_proc_file(file_path: &Path) -> Result<ProcessesInfo, ParseError>
                 if let Some((rss_str, virt_str)) = value_str.split_once('/') {
                     match parse_numeric::<u64>(rss_str, trimmed_line, file_path) {
                         Ok(val) => proc.memory_rss = Some(val),
-                        Err(e) => warn!("{}: {}", file_path.display(), e),
+                        Err(e) => ctx.value_error(e),
                     }
                     match parse_numeric::<u64>(virt_str, trimmed_line, file_path) {
                         Ok(val) => proc.memory_virt = Some(val),
-                        Err(e) => warn!("{}: {}", file_path.display(), e),
+                        Err(e) => ctx.value_error(e),
                     }
                 } else {
                     // Fallback if only one number is present? Or log warning.
-                    warn!(
-                        "{}: Malformed Memory line (expected 'rss/virt'): '{}'",
-                        file_path.display(),
-                        trimmed_line
-                    );
+                    ctx.warn_line(trimmed_line, "Malformed Memory line (expected 'rss/virt')");
                 }
             } else if let Some(value_str) = extract_value(trimmed_line, "Swap") {
                 match parse_numeric::<u64>(value_str, trimmed_line, file_path) {
                     Ok(val) => proc.swap = Some(val),
-                    Err(e) => warn!("{}: {}", file_path.display(), e),
+                    Err(e) => ctx.value_error(e),
                 }
             } else if let Some(value_str) = extract_value(trimmed_line, "Threads") {
                 match parse_numeric::<u32>(value_str, trimmed_line, file_path) {
                     Ok(val) => proc.threads = Some(val),
-                    Err(e) => warn!("{}: {}", file_path.display(), e),
+                    Err(e) => ctx.value_error(e),
                 }
             } else if let Some(value_str) = extract_value(trimmed_line, "Utime") {
                 match parse_numeric::<u64>(value_str, trimmed_line, file_path) {
                     Ok(val) => proc.utime = Some(val),
-                    Err(e) => warn!("{}: {}", file_path.display(), e),
+                    Err(e) => ctx.value_error(e),
+                }
+            } else if let Some(value_str) = extract_value(trimmed_line, "Start_time") {
+                match parse_numeric::<u64>(value_str, trimmed_line, file_path) {
+                    Ok(val) => proc.start_time = Some(val),
+                    Err(e) => ctx.value_error(e),
                 }
             } else if trimmed_line.starts_with("Max_cpus:") {
                 debug!(
@@ -403,19 +515,11 @@ async fn parse_proc_file(file_path: &Path) -> Result<ProcessesInfo, ParseError>
                     trimmed_line
                 );
             } else {
-                debug!(
-                    "{}: Ignoring unrecognized line within process block: {}",
-                    file_path.display(),
-                    trimmed_line
-                );
+                ctx.debug_line(trimmed_line, "Unrecognized line within process block");
             }
         } else {
             // Should not happen often if file format is consistent
-            debug!(
-                "{}: Ignoring unrecognized line outside process block: {}",
-                file_path.display(),
-                trimmed_line
-            );
+            ctx.debug_line(trimmed_line, "Unrecognized line outside process block");
         }
     }
 
@@ -434,14 +538,16 @@ async fn parse_proc_file(file_path: &Path) -> Result<ProcessesInfo, ParseError>
 }
 
 // --- New: Parses /tmp/ext_temp (External Temperature) ---
-async fn parse_ext_temp_file(file_path: &Path) -> Result<ExternalTemperatureInfo, ParseError> {
-    let content = fs::read_to_string(file_path)
-        .await
-        .map_err(|e| ParseError::Io(file_path.to_string_lossy().into_owned(), e))?;
-
-    let mut ext_temp_info = ExternalTemperatureInfo::default();
-
-    for line in content.lines() {
+// One file per sensor; the sensor's name and unit come from the settings.
+fn parse_ext_temp_file(
+    content: &str,
+    ctx: &mut ParseContext,
+) -> Result<ExternalSensorInfo, ParseError> {
+    let file_path = ctx.file_path;
+    let mut sensor_info = ExternalSensorInfo::default();
+
+    for (index, line) in content.lines().enumerate() {
+        ctx.next_line(index);
         let trimmed_line = line.trim();
         if trimmed_line.is_empty() {
             continue;
@@ -453,90 +559,423 @@ async fn parse_ext_temp_file(file_path: &Path) -> Result<ExternalTemperatureInfo
             let value_trimmed = val_str.trim();
             if !value_trimmed.is_empty() {
                 match parse_numeric::<f32>(value_trimmed, trimmed_line, file_path) {
-                    Ok(val) => ext_temp_info.temperature = Some(val),
-                    Err(e) => warn!("{}: {}", file_path.display(), e),
+                    Ok(val) => sensor_info.temperature = Some(val),
+                    Err(e) => ctx.value_error(e),
                 }
             } else {
-                warn!(
-                    "{}: Empty value found after 'Temp:' on line: '{}'",
-                    file_path.display(),
-                    trimmed_line
+                ctx.warn_line(trimmed_line, "Empty value after 'Temp:'");
+            }
+        } else if let Some(value_str) = extract_value(trimmed_line, "Humidity") {
+            match parse_numeric::<f32>(
+                value_str.trim_end_matches('%').trim(),
+                trimmed_line,
+                file_path,
+            ) {
+                Ok(val) => sensor_info.humidity = Some(val),
+                Err(e) => ctx.value_error(e),
+            }
+        } else {
+            // Ignore other lines quietly
+            ctx.debug_line(trimmed_line, "Unrecognized line");
+        }
+    }
+    Ok(sensor_info)
+}
+
+// How often to re-read a status file that was caught while being written
+#[derive(Debug, Clone, Copy)]
+pub struct ReadRetry {
+    pub attempts: u32,
+    // Delay before the second attempt, doubled for every further one
+    pub backoff: Duration,
+}
+
+fn torn(file_path: &Path, message: impl Into<String>) -> ParseError {
+    ParseError::Torn {
+        file: file_path.to_string_lossy().into_owned(),
+        message: message.into(),
+    }
+}
+
+// Writers that mark their snapshots start the file with "Seq: N" and end it
+// with "End: N". A file with the header but without the matching trailer was
+// cut off. Files without the header are taken as they are. Returns the number
+// of header lines removed along with the content.
+fn strip_markers<'a>(content: &'a str, file_path: &Path) -> Result<(usize, &'a str), ParseError> {
+    let Some(rest) = content.strip_prefix("Seq:") else {
+        return Ok((0, content));
+    };
+    let Some((seq, body)) = rest.split_once('\n') else {
+        return Err(torn(file_path, "incomplete sequence header"));
+    };
+    let seq = seq.trim();
+    let complete = body.ends_with('\n');
+    let (body, trailer) = body
+        .trim_end_matches('\n')
+        .rsplit_once('\n')
+        .unwrap_or(("", body.trim_end_matches('\n')));
+    match trailer.strip_prefix("End:").map(str::trim) {
+        Some(end) if complete && end == seq => Ok((1, body)),
+        _ => Err(torn(
+            file_path,
+            format!("no end marker for sequence {}", seq),
+        )),
+    }
+}
+
+// Parses /tmp/system (load average, tasks, uptime)
+fn parse_system_file(content: &str, ctx: &mut ParseContext) -> Result<SystemInfo, ParseError> {
+    let file_path = ctx.file_path;
+    let mut system_info = SystemInfo::default();
+
+    for (index, line) in content.lines().enumerate() {
+        ctx.next_line(index);
+        let trimmed_line = line.trim();
+        if trimmed_line.is_empty() {
+            continue;
+        }
+
+        // "Load: 0.15 0.10 0.05", same order as /proc/loadavg
+        if let Some(values) = trimmed_line.strip_prefix("Load:") {
+            let loads: Vec<&str> = values.split_whitespace().collect();
+            if loads.len() != 3 {
+                ctx.warn_line(trimmed_line, "Malformed Load line (expected 3 values)");
+                continue;
+            }
+            let targets = [
+                &mut system_info.load1,
+                &mut system_info.load5,
+                &mut system_info.load15,
+            ];
+            for (target, value_str) in targets.into_iter().zip(loads) {
+                match parse_numeric::<f32>(value_str, trimmed_line, file_path) {
+                    Ok(val) => *target = Some(val),
+                    Err(e) => ctx.value_error(e),
+                }
+            }
+        }
+        // "Tasks: 1/123", running/total
+        else if let Some(value_str) = extract_value(trimmed_line, "Tasks") {
+            let Some((running, total)) = value_str.split_once('/') else {
+                ctx.warn_line(
+                    trimmed_line,
+                    "Malformed Tasks line (expected 'running/total')",
                 );
+                continue;
+            };
+            match parse_numeric::<u32>(running, trimmed_line, file_path) {
+                Ok(val) => system_info.running_tasks = Some(val),
+                Err(e) => ctx.value_error(e),
+            }
+            match parse_numeric::<u32>(total, trimmed_line, file_path) {
+                Ok(val) => system_info.total_tasks = Some(val),
+                Err(e) => ctx.value_error(e),
+            }
+        } else if let Some(value_str) = extract_value(trimmed_line, "Uptime") {
+            match parse_numeric::<f64>(value_str, trimmed_line, file_path) {
+                Ok(val) => system_info.uptime_secs = Some(val),
+                Err(e) => ctx.value_error(e),
             }
-            // Assuming only one temp line per file, we can stop here
-            break;
         } else {
-            // Ignore other lines silently
-            debug!(
-                "{}: Ignoring unrecognized line: {}",
-                file_path.display(),
-                trimmed_line
-            );
+            ctx.debug_line(trimmed_line, "Unrecognized line");
+        }
+    }
+    Ok(system_info)
+}
+
+// Reads a file and checks that it did not change while being read. Returns
+// the content and the modification time of what was read.
+async fn read_stable(file_path: &Path) -> Result<(String, Option<u64>), ParseError> {
+    let io_error = |e| ParseError::Io(file_path.to_string_lossy().into_owned(), e);
+
+    let before = fs::metadata(file_path).await.map_err(io_error)?;
+    let content = fs::read_to_string(file_path).await.map_err(io_error)?;
+    let after = fs::metadata(file_path).await.map_err(io_error)?;
+
+    let mtime = after.modified().ok();
+    if before.len() != after.len() || before.modified().ok() != mtime {
+        return Err(torn(file_path, "file changed during the read"));
+    }
+    if content.len() as u64 != after.len() {
+        return Err(torn(
+            file_path,
+            format!("read {} of {} bytes", content.len(), after.len()),
+        ));
+    }
+    Ok((content, mtime.map(to_unix_ms)))
+}
+
+// Reads and parses a status file, retrying with backoff while it appears to be
+// mid-write so that only complete snapshots are published. JSON files are
+// detected by their content; anything else goes to the text parser `parse`.
+async fn read_status_file<T: JsonSection>(
+    file_path: &Path,
+    retry: &ReadRetry,
+    strict: bool,
+    parse: fn(&str, &mut ParseContext) -> Result<T, ParseError>,
+) -> Result<(T, Option<u64>, Vec<LineFinding>), ParseError> {
+    let mut backoff = retry.backoff;
+    let mut attempt = 1;
+    loop {
+        let result = read_stable(file_path).await.and_then(|(content, mtime)| {
+            let (line_offset, body) = strip_markers(&content, file_path)?;
+            if status_json::is_json(body) {
+                let parsed = status_json::parse(body, file_path)?;
+                return Ok((parsed, mtime, Vec::new()));
+            }
+            let mut ctx = ParseContext::new(file_path, strict, line_offset);
+            let parsed = parse(body, &mut ctx)?;
+            Ok((parsed, mtime, ctx.findings))
+        });
+        match result {
+            Err(e @ ParseError::Torn { .. }) if attempt < retry.attempts => {
+                debug!("{} (attempt {}), retrying in {:?}", e, attempt, backoff);
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+                attempt += 1;
+            }
+            result => return result,
         }
     }
-    Ok(ext_temp_info)
 }
 
-pub async fn read_status_files(
-    cpu_path: &Path,
-    ram_path: &Path,
-    proc_path: &Path,
-    ext_temp_path: &Path,
-) -> SystemStatus {
+pub async fn read_status_files(source: &StatusFileSource, sections: &[Section]) -> Collection {
+    let StatusFileSource {
+        cpu_file: cpu_path,
+        ram_file: ram_path,
+        proc_file: proc_path,
+        ext_sensors,
+        system_file: system_path,
+        read_retry: retry,
+        strict_parsing: strict,
+        ..
+    } = source;
+    let strict = *strict;
     debug!(
-        "Reading status files: CPU='{}', RAM='{}', Proc='{}', ExtTemp='{}'", // Renamed log slightly
+        "Reading status files: CPU='{}', RAM='{}', Proc='{}', ExtTemp='{}', System='{}'",
         cpu_path.display(),
         ram_path.display(),
         proc_path.display(),
-        ext_temp_path.display()
+        ExtSensorList(ext_sensors.clone()),
+        system_path.display()
     );
 
-    let mut system_status = SystemStatus::default();
+    let mut collection = Collection::default();
 
-    match parse_cpu_file(cpu_path).await {
-        Ok(cpu_info) => {
-            system_status.cpu = cpu_info;
-        }
-        Err(e) => {
-            error!("Failed to parse CPU file '{}': {}", cpu_path.display(), e);
+    if sections.contains(&Section::Cpu) {
+        match read_status_file(cpu_path, retry, strict, parse_cpu_file).await {
+            Ok((cpu_info, mtime, findings)) => {
+                collection.status.cpu = cpu_info;
+                collection.status.mark_collected(Section::Cpu, mtime);
+                if strict {
+                    collection.record_findings(Section::Cpu, cpu_path, findings);
+                }
+            }
+            Err(e) => {
+                collection.record_error(Section::Cpu, &e);
+                error!("Failed to parse CPU file '{}': {}", cpu_path.display(), e);
+            }
         }
     }
 
-    match parse_ram_file(ram_path).await {
-        Ok(memory_info) => {
-            system_status.memory = memory_info;
+    if sections.contains(&Section::Memory) {
+        match read_status_file(ram_path, retry, strict, parse_ram_file).await {
+            Ok((memory_info, mtime, findings)) => {
+                collection.status.memory = memory_info;
+                collection.status.mark_collected(Section::Memory, mtime);
+                if strict {
+                    collection.record_findings(Section::Memory, ram_path, findings);
+                }
+            }
+            Err(e) => {
+                collection.record_error(Section::Memory, &e);
+                error!("Failed to parse RAM file '{}': {}", ram_path.display(), e);
+            }
         }
-        Err(e) => {
-            error!("Failed to parse RAM file '{}': {}", ram_path.display(), e);
+    }
+
+    if sections.contains(&Section::Processes) {
+        match read_status_file(proc_path, retry, strict, parse_proc_file).await {
+            Ok((processes_info, mtime, findings)) => {
+                collection.status.processes = processes_info;
+                collection.status.mark_collected(Section::Processes, mtime);
+                if strict {
+                    collection.record_findings(Section::Processes, proc_path, findings);
+                }
+            }
+            Err(e) => {
+                collection.record_error(Section::Processes, &e);
+                error!(
+                    "Failed to parse Process file '{}': {}",
+                    proc_path.display(),
+                    e
+                );
+            }
         }
     }
 
-    match parse_proc_file(proc_path).await {
-        Ok(processes_info) => {
-            system_status.processes = processes_info;
+    if sections.contains(&Section::ExternalTemperature) {
+        let mut ext_temp_info = ExternalTemperatureInfo::default();
+        // The oldest file decides whether the section is stale
+        let mut oldest_mtime: Option<u64> = None;
+        let mut any_read = false;
+        for sensor in ext_sensors {
+            let reading = match read_status_file(&sensor.file, retry, strict, parse_ext_temp_file)
+                .await
+            {
+                Ok((mut reading, mtime, findings)) => {
+                    reading.read_at = mtime;
+                    any_read = true;
+                    if let Some(mtime) = mtime {
+                        oldest_mtime = Some(oldest_mtime.map_or(mtime, |oldest| oldest.min(mtime)));
+                    }
+                    if strict {
+                        collection.record_findings(
+                            Section::ExternalTemperature,
+                            &sensor.file,
+                            findings,
+                        );
+                    }
+                    reading
+                }
+                Err(e) => {
+                    collection.record_error(Section::ExternalTemperature, &e);
+                    error!(
+                        "Failed to parse External Temperature file '{}' of sensor '{}': {}",
+                        sensor.file.display(),
+                        sensor.name,
+                        e
+                    );
+                    ExternalSensorInfo {
+                        error: Some(e.to_string()),
+                        ..Default::default()
+                    }
+                }
+            };
+            ext_temp_info.sensors.push(ExternalSensorInfo {
+                name: sensor.name.clone(),
+                unit: sensor.unit,
+                ..reading
+            });
         }
-        Err(e) => {
-            error!(
-                "Failed to parse Process file '{}': {}",
-                proc_path.display(),
-                e
-            );
+        ext_temp_info.temperature = ext_temp_info.sensors.first().and_then(|s| s.celsius());
+        collection.status.external_temperature = ext_temp_info;
+        // Sensors that failed are reported per sensor as long as one could
+        // be read
+        if any_read {
+            collection
+                .status
+                .mark_collected(Section::ExternalTemperature, oldest_mtime);
         }
     }
 
-    match parse_ext_temp_file(ext_temp_path).await {
-        Ok(ext_temp_info) => {
-            system_status.external_temperature = ext_temp_info;
+    if sections.contains(&Section::System) {
+        match read_status_file(system_path, retry, strict, parse_system_file).await {
+            Ok((mut system_info, mtime, findings)) => {
+                // The uptime was sampled when the file was written
+                system_info.boot_time = system_info
+                    .uptime_secs
+                    .zip(mtime)
+                    .map(|(uptime, mtime)| mtime.saturating_sub((uptime * 1000.0) as u64));
+                collection.status.system = system_info;
+                collection.status.mark_collected(Section::System, mtime);
+                if strict {
+                    collection.record_findings(Section::System, system_path, findings);
+                }
+            }
+            Err(e) => {
+                collection.record_error(Section::System, &e);
+                error!(
+                    "Failed to parse System file '{}': {}",
+                    system_path.display(),
+                    e
+                );
+            }
         }
-        Err(e) => {
-            error!(
-                "Failed to parse External Temperature file '{}': {}",
-                ext_temp_path.display(),
-                e
-            );
+    }
+
+    debug!(
+        "Status file parsing complete. Status: {:?}",
+        collection.status
+    );
+    collection
+}
+
+// Status files written by the rpi_watch daemon
+#[derive(Debug, Clone)]
+pub struct StatusFileSource {
+    pub cpu_file: PathBuf,
+    pub ram_file: PathBuf,
+    pub proc_file: PathBuf,
+    pub ext_sensors: Vec<ExtSensorConfig>,
+    pub system_file: PathBuf,
+    pub procfs_root: PathBuf,
+    pub sysfs_root: PathBuf,
+    pub read_retry: ReadRetry,
+    pub strict_parsing: bool,
+}
+
+impl StatusSource for StatusFileSource {
+    fn name(&self) -> &'static str {
+        "files"
+    }
+
+    fn capabilities(&self) -> SourceCapabilities {
+        SourceCapabilities {
+            cpu: true,
+            memory: true,
+            processes: true,
+            external_temperature: !self.ext_sensors.is_empty(),
+            system: true,
+            // Read from the kernel, see disks.rs, network.rs and thermal.rs
+            disks: true,
+            network: true,
+            thermal: true,
+            // rpi_watch writes the difference since its previous sample
+            cpu_counters: CounterKind::Interval,
         }
     }
 
-    debug!("Status file parsing complete. Status: {:?}", system_status);
-    system_status
+    fn collect(&self) -> BoxFuture<'_, Collection> {
+        self.collect_sections(&Section::ALL)
+    }
+
+    // rpi_watch does not report disks, network interfaces or on-board
+    // sensors, so those sections are read from the kernel just as the procfs
+    // source does, see disks.rs, network.rs and thermal.rs.
+    fn collect_sections(&self, sections: &[Section]) -> BoxFuture<'_, Collection> {
+        let sections = sections.to_vec();
+        Box::pin(async move {
+            let mut collection = read_status_files(self, &sections).await;
+            if sections.contains(&Section::Disks) {
+                collect_disks(&mut collection, &self.procfs_root).await;
+            }
+            if sections.contains(&Section::Network) {
+                collect_network(&mut collection, &self.procfs_root, &self.sysfs_root).await;
+            }
+            if sections.contains(&Section::Thermal) {
+                collect_thermal(&mut collection, &self.sysfs_root).await;
+            }
+            collection
+        })
+    }
+
+    fn process_identity(&self, pid: u32) -> BoxFuture<'_, Option<ProcessIdentity>> {
+        Box::pin(read_process_identity(&self.procfs_root, pid))
+    }
+
+    fn watched_files(&self) -> Vec<(Section, PathBuf)> {
+        let mut files = vec![
+            (Section::Cpu, self.cpu_file.clone()),
+            (Section::Memory, self.ram_file.clone()),
+            (Section::Processes, self.proc_file.clone()),
+            (Section::System, self.system_file.clone()),
+        ];
+        files.extend(
+            self.ext_sensors
+                .iter()
+                .map(|sensor| (Section::ExternalTemperature, sensor.file.clone())),
+        );
+        files
+    }
 }
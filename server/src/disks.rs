@@ -0,0 +1,208 @@
+use crate::data_source::ParseError;
+use crate::kernel_fs::read_file;
+use crate::models::{BlockDeviceInfo, DisksInfo, FilesystemInfo, Section};
+use crate::source::Collection;
+use std::{ffi::CString, io, os::unix::ffi::OsStrExt, path::Path};
+use tracing::{debug, error, warn};
+
+// Mounted filesystems from <procfs>/mounts plus statvfs, and block device
+// counters from <procfs>/diskstats.
+
+// Undoes the octal escapes of /proc/mounts ("\040" for a space).
+fn unescape_mount_field(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let code = bytes
+            .get(i + 1..i + 4)
+            .filter(|_| bytes[i] == b'\\')
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u8::from_str_radix(digits, 8).ok());
+        match code {
+            Some(code) => {
+                out.push(code);
+                i += 4;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[derive(Debug)]
+struct Mount {
+    device: String,
+    mount_point: String,
+    fs_type: String,
+    read_only: bool,
+}
+
+fn parse_mounts(content: &str, file_path: &Path) -> Vec<Mount> {
+    let mut mounts: Vec<Mount> = Vec::new();
+    for line in content.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 4 {
+            warn!(
+                "{}: skipping malformed mount line '{}'",
+                file_path.display(),
+                line
+            );
+            continue;
+        }
+        let mount = Mount {
+            device: unescape_mount_field(fields[0]),
+            mount_point: unescape_mount_field(fields[1]),
+            fs_type: fields[2].to_string(),
+            read_only: fields[3].split(',').any(|option| option == "ro"),
+        };
+        // A later mount on the same point hides the earlier one
+        mounts.retain(|m| m.mount_point != mount.mount_point);
+        mounts.push(mount);
+    }
+    mounts
+}
+
+fn statvfs(path: &Path) -> io::Result<libc::statvfs> {
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `c_path` is NUL-terminated and `stat` is only read after the
+    // call reported success.
+    let result = unsafe { libc::statvfs(c_path.as_ptr(), stat.as_mut_ptr()) };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { stat.assume_init() })
+}
+
+fn percent(part: u64, whole: u64) -> Option<f32> {
+    (whole > 0).then(|| (part as f64 * 100.0 / whole as f64) as f32)
+}
+
+// The statvfs field types are 32 bit on some targets (armv7) and 64 bit on
+// others, so the conversions are only useless on the latter
+#[allow(clippy::useless_conversion)]
+fn filesystem_info(mount: Mount) -> Option<FilesystemInfo> {
+    let stat = match statvfs(Path::new(&mount.mount_point)) {
+        Ok(stat) => stat,
+        Err(e) => {
+            debug!("statvfs '{}' failed: {}", mount.mount_point, e);
+            return None;
+        }
+    };
+    // proc, sysfs, cgroup and other pseudo filesystems have no blocks
+    if stat.f_blocks == 0 {
+        return None;
+    }
+
+    let kb = |blocks| u64::from(blocks) * u64::from(stat.f_frsize) / 1024;
+    let total = kb(stat.f_blocks);
+    let used = kb(stat.f_blocks - stat.f_bfree);
+    let available = kb(stat.f_bavail);
+    let inodes_total = u64::from(stat.f_files);
+    let inodes_free = u64::from(stat.f_ffree);
+    let inodes_used = inodes_total.saturating_sub(inodes_free);
+
+    Some(FilesystemInfo {
+        device: mount.device,
+        mount_point: mount.mount_point,
+        fs_type: mount.fs_type,
+        read_only: mount.read_only,
+        total,
+        used,
+        available,
+        used_percent: percent(used, used + available),
+        inodes_total,
+        inodes_used,
+        inodes_free,
+        inodes_used_percent: percent(inodes_used, inodes_total),
+    })
+}
+
+fn parse_diskstats(content: &str, file_path: &Path) -> Vec<BlockDeviceInfo> {
+    let mut devices = Vec::new();
+    for line in content.lines() {
+        // major minor name, then at least 11 counters
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 14 {
+            warn!(
+                "{}: skipping malformed line '{}'",
+                file_path.display(),
+                line
+            );
+            continue;
+        }
+        let counter = |index: usize| fields[index].parse::<u64>();
+        let device = match (counter(3), counter(5), counter(7), counter(9), counter(12)) {
+            (Ok(reads), Ok(sectors_read), Ok(writes), Ok(sectors_written), Ok(io_time_ms)) => {
+                BlockDeviceInfo {
+                    name: fields[2].to_string(),
+                    reads_completed: reads,
+                    sectors_read,
+                    writes_completed: writes,
+                    sectors_written,
+                    io_time_ms,
+                    ..Default::default()
+                }
+            }
+            _ => {
+                warn!(
+                    "{}: skipping line with non-numeric counters '{}'",
+                    file_path.display(),
+                    line
+                );
+                continue;
+            }
+        };
+        // Unused ram and loop devices
+        if device.reads_completed == 0 && device.writes_completed == 0 {
+            continue;
+        }
+        devices.push(device);
+    }
+    devices
+}
+
+pub async fn read_disks_info(procfs_root: &Path) -> Result<DisksInfo, ParseError> {
+    let mounts_path = procfs_root.join("mounts");
+    let mounts = parse_mounts(&read_file(&mounts_path).await?, &mounts_path);
+    let diskstats_path = procfs_root.join("diskstats");
+    let devices = parse_diskstats(&read_file(&diskstats_path).await?, &diskstats_path);
+
+    // statvfs blocks while a network filesystem does not respond
+    let filesystems = tokio::task::spawn_blocking(move || {
+        mounts
+            .into_iter()
+            .filter_map(filesystem_info)
+            .collect::<Vec<_>>()
+    })
+    .await
+    .map_err(|e| {
+        ParseError::Io(
+            mounts_path.to_string_lossy().into_owned(),
+            io::Error::other(e),
+        )
+    })?;
+
+    Ok(DisksInfo {
+        filesystems,
+        devices,
+    })
+}
+
+pub async fn collect_disks(collection: &mut Collection, procfs_root: &Path) {
+    match read_disks_info(procfs_root).await {
+        Ok(disks_info) => {
+            collection.status.disks = disks_info;
+            collection.status.mark_collected(Section::Disks, None);
+        }
+        Err(e) => {
+            error!("Failed to read disk statistics: {}", e);
+            collection.record_error(Section::Disks, &e);
+        }
+    }
+}
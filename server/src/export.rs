@@ -0,0 +1,301 @@
+use crate::history::{SectionSamples, HISTORY_SECTIONS};
+use crate::models::Section;
+use crate::store::Store;
+use serde::Deserialize;
+use std::{collections::HashMap, io, str::FromStr, sync::Arc};
+use thiserror::Error;
+use tokio::sync::mpsc;
+use tracing::{debug, error};
+
+// Recorded samples as CSV or NDJSON, one row per refresh, for reports and
+// spreadsheets. Columns are "{section}.{series}" with the series names of
+// GET /history. They follow the model structs and the cores, sensors and
+// thermal devices of the current snapshot, not the samples in the range, so
+// every export of a system has the same columns.
+
+// Bytes collected before a chunk is sent to the client
+const CHUNK_SIZE: usize = 64 * 1024;
+// Stored samples are read one window at a time, so the store stays
+// available to the refresh while a slow client downloads
+const STORE_WINDOW_MS: u64 = 3_600_000;
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Ndjson,
+}
+
+impl ExportFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Ndjson => "application/x-ndjson",
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ExportError {
+    #[error("{0}")]
+    UnknownSection(String),
+    #[error("No samples are recorded for section '{0}'")]
+    NotRecorded(Section),
+    #[error("'from' ({from}) is after 'to' ({to})")]
+    InvalidRange { from: u64, to: u64 },
+}
+
+// Query string of GET /export, times in Unix epoch milliseconds, e.g.
+// ?sections=cpu,ext_temp&from=1717171000000&format=ndjson
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ExportQuery {
+    // Comma-separated section names; all recorded sections when absent
+    sections: Option<String>,
+    from: Option<u64>,
+    to: Option<u64>,
+    #[serde(default)]
+    format: ExportFormat,
+}
+
+// A validated `ExportQuery`
+#[derive(Debug, Clone)]
+pub struct ExportRange {
+    pub sections: Vec<Section>,
+    pub from: u64,
+    pub to: u64,
+    pub format: ExportFormat,
+}
+
+impl ExportQuery {
+    // Fills in the defaults: every recorded section and sample up to `now`.
+    pub fn resolve(&self, now: u64) -> Result<ExportRange, ExportError> {
+        let sections = match &self.sections {
+            None => HISTORY_SECTIONS.to_vec(),
+            Some(list) => {
+                let mut sections = Vec::new();
+                for name in list.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+                    let section = Section::from_str(name).map_err(ExportError::UnknownSection)?;
+                    if !HISTORY_SECTIONS.contains(&section) {
+                        return Err(ExportError::NotRecorded(section));
+                    }
+                    if !sections.contains(&section) {
+                        sections.push(section);
+                    }
+                }
+                sections
+            }
+        };
+        let to = self.to.unwrap_or(now);
+        let from = self.from.unwrap_or(0);
+        if from > to {
+            return Err(ExportError::InvalidRange { from, to });
+        }
+        Ok(ExportRange {
+            sections,
+            from,
+            to,
+            format: self.format,
+        })
+    }
+}
+
+// Samples of one section from the in-memory history, and the time of its
+// oldest sample; older ones come from the store.
+#[derive(Debug)]
+pub struct MemorySection {
+    pub section: Section,
+    // Series names of the section, see `series_names`
+    pub columns: Vec<String>,
+    pub oldest: Option<u64>,
+    pub samples: SectionSamples,
+}
+
+// Quotes a CSV field when it contains a separator, quote or line break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+// Collects samples into rows, one per time, and formats them into chunks.
+struct RowWriter {
+    format: ExportFormat,
+    columns: Vec<String>,
+    index: HashMap<(Section, String), usize>,
+    at: Option<u64>,
+    values: Vec<Option<f64>>,
+    buffer: String,
+}
+
+impl RowWriter {
+    fn new(format: ExportFormat) -> Self {
+        RowWriter {
+            format,
+            columns: Vec::new(),
+            index: HashMap::new(),
+            at: None,
+            values: Vec::new(),
+            buffer: String::new(),
+        }
+    }
+
+    fn add_column(&mut self, section: Section, name: &str) {
+        let key = (section, name.to_string());
+        if !self.index.contains_key(&key) {
+            self.index.insert(key, self.columns.len());
+            self.columns.push(format!("{}.{}", section.as_str(), name));
+        }
+    }
+
+    fn write_header(&mut self) {
+        self.values = vec![None; self.columns.len()];
+        if self.format == ExportFormat::Csv {
+            self.buffer.push_str("timestamp");
+            for column in &self.columns {
+                self.buffer.push(',');
+                self.buffer.push_str(&csv_field(column));
+            }
+            self.buffer.push('\n');
+        }
+    }
+
+    fn push(&mut self, at: u64, section: Section, name: &str, value: f64) {
+        if self.at != Some(at) {
+            self.finish_row();
+            self.at = Some(at);
+        }
+        // Columns are fixed before the first row; a series outside them,
+        // such as a sensor no longer configured, is left out
+        if let Some(&column) = self.index.get(&(section, name.to_string())) {
+            self.values[column] = Some(value);
+        }
+    }
+
+    fn finish_row(&mut self) {
+        let Some(at) = self.at.take() else {
+            return;
+        };
+        match self.format {
+            ExportFormat::Csv => {
+                self.buffer.push_str(&at.to_string());
+                for value in &self.values {
+                    self.buffer.push(',');
+                    if let Some(value) = value {
+                        self.buffer.push_str(&value.to_string());
+                    }
+                }
+            }
+            ExportFormat::Ndjson => {
+                self.buffer.push_str(&format!("{{\"timestamp\":{}", at));
+                for (column, value) in self.columns.iter().zip(&self.values) {
+                    let key = serde_json::to_string(column).unwrap_or_default();
+                    let value = serde_json::to_string(value).unwrap_or_default();
+                    self.buffer.push_str(&format!(",{}:{}", key, value));
+                }
+                self.buffer.push('}');
+            }
+        }
+        self.buffer.push('\n');
+        self.values.iter_mut().for_each(|value| *value = None);
+    }
+
+    // Sends the buffer once it is large enough, or always with `force`.
+    // Returns false when the client has gone away.
+    fn send(&mut self, tx: &mpsc::Sender<io::Result<String>>, force: bool) -> bool {
+        if self.buffer.is_empty() || (!force && self.buffer.len() < CHUNK_SIZE) {
+            return true;
+        }
+        tx.blocking_send(Ok(std::mem::take(&mut self.buffer)))
+            .is_ok()
+    }
+}
+
+// Writes the export into `tx`: stored samples older than the in-memory
+// history first, then the in-memory ones. Runs on a blocking thread. A store
+// failure ends the stream with an error, so the client sees a truncated
+// download instead of a complete-looking one.
+pub fn write_export(
+    range: ExportRange,
+    memory: Vec<MemorySection>,
+    store: Option<Arc<Store>>,
+    tx: mpsc::Sender<io::Result<String>>,
+) {
+    let mut writer = RowWriter::new(range.format);
+
+    // The part of each section's range that only the store holds
+    let store_parts: Vec<(Section, u64, u64)> = match &store {
+        Some(_) => memory
+            .iter()
+            .filter_map(|part| {
+                let to = part
+                    .oldest
+                    .map_or(range.to, |oldest| range.to.min(oldest.saturating_sub(1)));
+                (range.from <= to).then_some((part.section, range.from, to))
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+
+    for part in &memory {
+        for name in &part.columns {
+            writer.add_column(part.section, name);
+        }
+    }
+    writer.write_header();
+
+    if let (Some(store), false) = (&store, store_parts.is_empty()) {
+        let store_to = store_parts.iter().map(|&(_, _, to)| to).max().unwrap_or(0);
+        let mut window_from = range.from;
+        while window_from <= store_to {
+            let window_to = store_to.min(window_from.saturating_add(STORE_WINDOW_MS - 1));
+            let samples = match store.raw_samples(&store_parts, window_from, window_to) {
+                Ok(samples) => samples,
+                Err(e) => {
+                    error!("Export: cannot read the metric store: {}", e);
+                    // The rows sent so far cannot be taken back
+                    writer.send(&tx, true);
+                    let _ = tx.blocking_send(Err(io::Error::other(e.to_string())));
+                    return;
+                }
+            };
+            for (at, section, name, value) in samples {
+                writer.push(at, section, &name, value);
+                if !writer.send(&tx, false) {
+                    debug!("Export: client went away");
+                    return;
+                }
+            }
+            window_from = window_to.saturating_add(1);
+        }
+    }
+
+    // Sections are recorded at the same times when refreshed together, so
+    // their samples merge into one row
+    let mut samples: Vec<_> = memory
+        .iter()
+        .enumerate()
+        .flat_map(|(i, part)| {
+            part.samples
+                .samples
+                .iter()
+                .map(move |(at, values)| (*at, i, values))
+        })
+        .collect();
+    samples.sort_by_key(|&(at, i, _)| (at, i));
+    for (at, i, values) in samples {
+        let part = &memory[i];
+        for &(index, value) in values {
+            writer.push(at, part.section, &part.samples.names[index as usize], value);
+        }
+        if !writer.send(&tx, false) {
+            debug!("Export: client went away");
+            return;
+        }
+    }
+    writer.finish_row();
+    writer.send(&tx, true);
+}
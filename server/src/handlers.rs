@@ -1,45 +1,457 @@
-use crate::{controller::*, models::*, AppState};
-use axum::{extract::State, http::StatusCode, Json};
-use serde::Deserialize;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use crate::clock::unix_now_ms;
+use crate::export::{self, ExportQuery, MemorySection};
+use crate::history::{series_names, Buckets, HistoryError, HistoryQuery, HISTORY_SECTIONS};
+use crate::metrics::{self, MetricsFormat, MetricsInput};
+use crate::{
+    controller::*,
+    models::*,
+    process_query::ProcessQuery,
+    process_tree,
+    source::{DetailsLookup, OPTIONAL_SECTIONS},
+    status_stream, AppState,
+};
+use axum::{
+    body::Body,
+    extract::{rejection::QueryRejection, ws::WebSocketUpgrade, Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, io, str::FromStr, sync::Arc, time::Duration};
+use tokio::sync::{mpsc, RwLock};
 use tracing::{debug, error};
 
 // --- Status Handlers ---
+
+// Freshness of the section a single-section endpoint serves, as headers so
+// the JSON bodies stay unchanged.
+fn freshness_headers(status: &SystemStatus, section: Section) -> HeaderMap {
+    let freshness = status.freshness.get(&section).cloned().unwrap_or_default();
+    let state = match freshness.state {
+        Freshness::Fresh => "fresh",
+        Freshness::Stale => "stale",
+        Freshness::Missing => "missing",
+    };
+    let mut headers = HeaderMap::new();
+    headers.insert("x-data-freshness", HeaderValue::from_static(state));
+    if let Some(collected_at) = freshness.collected_at {
+        headers.insert("x-collected-at", HeaderValue::from(collected_at));
+    }
+    if let Some(source_mtime) = freshness.source_mtime {
+        headers.insert("x-source-mtime", HeaderValue::from(source_mtime));
+    }
+    headers
+}
+
+pub async fn get_parse_errors(
+    State(state): State<Arc<RwLock<AppState>>>,
+) -> Result<Json<Vec<ParseErrorRecord>>, StatusCode> {
+    debug!("Handling /diagnostics/errors request");
+    let app_state = state.read().await;
+    Ok(Json(app_state.parse_errors.clone()))
+}
+
+pub async fn get_parse_diagnostics(
+    State(state): State<Arc<RwLock<AppState>>>,
+) -> Result<Json<ParseDiagnostics>, StatusCode> {
+    debug!("Handling /diagnostics/parse request");
+    let app_state = state.read().await;
+    Ok(Json(app_state.parse_diagnostics.clone()))
+}
+
+pub async fn get_health(
+    State(state): State<Arc<RwLock<AppState>>>,
+) -> (StatusCode, Json<HealthResponse>) {
+    debug!("Handling /health request");
+    let app_state = state.read().await;
+    let status = &app_state.system_status;
+    let capabilities = app_state.status_source.capabilities();
+
+    // Sections the source cannot provide do not count against health, nor
+    // do optional ones the board has never had
+    let healthy = Section::ALL
+        .iter()
+        .filter(|section| capabilities.provides(**section))
+        .filter(|section| {
+            !OPTIONAL_SECTIONS.contains(section) || app_state.collected_sections.contains(section)
+        })
+        .all(|section| {
+            status
+                .freshness
+                .get(section)
+                .is_some_and(|f| f.state == Freshness::Fresh)
+        });
+    let response = HealthResponse {
+        status: if healthy { "ok" } else { "degraded" }.to_string(),
+        seq: status.seq,
+        collected_at: status.collected_at,
+        sections: status.freshness.clone(),
+    };
+    let code = if healthy {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (code, Json(response))
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct StatusQuery {
+    // Comma-separated, e.g. "cpu,memory"; all sections when absent
+    sections: Option<String>,
+}
+
+pub async fn get_status(
+    State(state): State<Arc<RwLock<AppState>>>,
+    query: Result<Query<StatusQuery>, QueryRejection>,
+) -> Result<Json<StatusResponse>, (StatusCode, String)> {
+    debug!("Handling /status request");
+    let Query(query) = query.map_err(|e| (StatusCode::BAD_REQUEST, e.body_text()))?;
+    let sections = match query.sections.as_deref() {
+        Some(list) => parse_sections(list).map_err(|e| (StatusCode::BAD_REQUEST, e))?,
+        None => Section::ALL.to_vec(),
+    };
+    let app_state = state.read().await;
+    Ok(Json(StatusResponse::from_status(
+        &app_state.system_status,
+        &sections,
+    )))
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct StatusStreamQuery {
+    // Initial subscription, as for /status; all sections when absent
+    sections: Option<String>,
+    // Slower than the server's minimum, never faster
+    min_interval_ms: Option<u64>,
+}
+
+pub async fn get_status_ws(
+    State(state): State<Arc<RwLock<AppState>>>,
+    query: Result<Query<StatusStreamQuery>, QueryRejection>,
+    ws: WebSocketUpgrade,
+) -> Result<Response, (StatusCode, String)> {
+    debug!("Handling /status/ws request");
+    let Query(query) = query.map_err(|e| (StatusCode::BAD_REQUEST, e.body_text()))?;
+    let sections = match query.sections.as_deref() {
+        Some(list) => parse_sections(list).map_err(|e| (StatusCode::BAD_REQUEST, e))?,
+        None => Section::ALL.to_vec(),
+    };
+    let (current, updates, min_interval) = {
+        let app_state = state.read().await;
+        // Subscribed under the lock, so no refresh falls between the
+        // snapshot and the first update
+        let min_interval = query
+            .min_interval_ms
+            .map(Duration::from_millis)
+            .map_or(app_state.ws_min_interval, |interval| {
+                interval.max(app_state.ws_min_interval)
+            });
+        (
+            Arc::new(app_state.system_status.clone()),
+            app_state.status_updates.subscribe(),
+            min_interval,
+        )
+    };
+    Ok(ws.on_upgrade(move |socket| {
+        status_stream::serve_client(socket, current, updates, sections, min_interval)
+    }))
+}
+
+pub async fn get_history(
+    State(state): State<Arc<RwLock<AppState>>>,
+    Path(section): Path<String>,
+    query: Result<Query<HistoryQuery>, QueryRejection>,
+) -> Result<Json<HistoryResponse>, (StatusCode, String)> {
+    debug!("Handling /history/{} request", section);
+    let section = Section::from_str(&section).map_err(|e| (StatusCode::NOT_FOUND, e))?;
+    let Query(query) = query.map_err(|e| (StatusCode::BAD_REQUEST, e.body_text()))?;
+    let to_response = |e: HistoryError| match e {
+        HistoryError::NoHistory(_) => (StatusCode::NOT_FOUND, e.to_string()),
+        HistoryError::Store(_) => {
+            error!("{}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+        }
+        _ => (StatusCode::BAD_REQUEST, e.to_string()),
+    };
+
+    let (range, mut buckets, store, memory_oldest) = {
+        let app_state = state.read().await;
+        // Processes are only kept by the store
+        let kept = HISTORY_SECTIONS.contains(&section)
+            || (section == Section::Processes && app_state.store.is_some());
+        if !kept {
+            return Err(to_response(HistoryError::NoHistory(section)));
+        }
+        let range = query
+            .resolve(unix_now_ms(), app_state.history.retention_ms())
+            .map_err(to_response)?;
+        let mut buckets = Buckets::default();
+        app_state.history.accumulate(section, &range, &mut buckets);
+        let memory_oldest = app_state.history.oldest(section);
+        (range, buckets, app_state.store.clone(), memory_oldest)
+    };
+
+    // The part of the range from before the in-memory history
+    if let Some(store) = store {
+        let store_to =
+            memory_oldest.map_or(range.to, |oldest| range.to.min(oldest.saturating_sub(1)));
+        if range.from <= store_to {
+            let store_range = range.truncated(store_to);
+            buckets = tokio::task::spawn_blocking(move || {
+                store
+                    .accumulate(section, &store_range, &mut buckets)
+                    .map(|()| buckets)
+            })
+            .await
+            .map_err(|e| HistoryError::Store(e.to_string()))
+            .and_then(|result| result.map_err(|e| HistoryError::Store(e.to_string())))
+            .map_err(to_response)?;
+        }
+    }
+    Ok(Json(buckets.into_response(section, &range)))
+}
+
+pub async fn get_export(
+    State(state): State<Arc<RwLock<AppState>>>,
+    query: Result<Query<ExportQuery>, QueryRejection>,
+) -> Result<Response, (StatusCode, String)> {
+    debug!("Handling /export request");
+    let Query(query) = query.map_err(|e| (StatusCode::BAD_REQUEST, e.body_text()))?;
+    let mut range = query
+        .resolve(unix_now_ms())
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+
+    let (memory, store) = {
+        let app_state = state.read().await;
+        let memory: Vec<MemorySection> = range
+            .sections
+            .iter()
+            .map(|&section| MemorySection {
+                section,
+                columns: series_names(&app_state.system_status, section),
+                oldest: app_state.history.oldest(section),
+                samples: app_state.history.samples(section, range.from, range.to),
+            })
+            .collect();
+        (memory, app_state.store.clone())
+    };
+
+    // Only raw samples are exported; a range reaching back to where the
+    // store keeps rollups only starts at the oldest raw sample instead
+    let mut available_from = memory.iter().filter_map(|part| part.oldest).min();
+    if let Some(store) = store.clone() {
+        let oldest_raw = tokio::task::spawn_blocking(move || store.oldest_raw())
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|result| result.map_err(|e| e.to_string()))
+            .map_err(|e| {
+                error!("Export: cannot read the metric store: {}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, e)
+            })?;
+        available_from = available_from.into_iter().chain(oldest_raw).min();
+    }
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(range.format.content_type()),
+    );
+    if let Some(available_from) = available_from.filter(|&from| range.from < from) {
+        range.from = available_from;
+        headers.insert("x-export-from", HeaderValue::from(available_from));
+    }
+
+    // Rows are produced on a blocking thread, since older ones are read
+    // from the store, and sent as they are ready
+    let (tx, rx) = mpsc::channel::<io::Result<String>>(4);
+    tokio::task::spawn_blocking(move || export::write_export(range, memory, store, tx));
+    let chunks = futures_util::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|chunk| (chunk, rx))
+    });
+    Ok((headers, Body::from_stream(chunks)).into_response())
+}
+
+pub async fn get_metrics(
+    State(state): State<Arc<RwLock<AppState>>>,
+    headers: HeaderMap,
+) -> Response {
+    debug!("Handling /metrics request");
+    let format = MetricsFormat::from_accept(
+        headers
+            .get(header::ACCEPT)
+            .and_then(|accept| accept.to_str().ok()),
+    );
+
+    let app_state = state.read().await;
+    let body = metrics::render(
+        &MetricsInput {
+            status: &app_state.system_status,
+            cpu_totals: &app_state.cpu_totals,
+            parse_errors: &app_state.parse_errors,
+            controller: app_state.controller_client.last_contact(),
+            max_processes: app_state.metrics_max_processes,
+        },
+        format,
+    );
+    ([(header::CONTENT_TYPE, format.content_type())], body).into_response()
+}
+
 pub async fn get_cpu_info(
     State(state): State<Arc<RwLock<AppState>>>,
-) -> Result<Json<CpuInfo>, StatusCode> {
+) -> Result<(HeaderMap, Json<CpuInfo>), StatusCode> {
     debug!("Handling /cpu request");
     let app_state = state.read().await;
     let cpu_info = app_state.system_status.cpu.clone();
-    Ok(Json(cpu_info))
+    let headers = freshness_headers(&app_state.system_status, Section::Cpu);
+    Ok((headers, Json(cpu_info)))
 }
 
 pub async fn get_memory_info(
     State(state): State<Arc<RwLock<AppState>>>,
-) -> Result<Json<MemoryInfo>, StatusCode> {
+) -> Result<(HeaderMap, Json<MemoryInfo>), StatusCode> {
     debug!("Handling /memory request");
     let app_state = state.read().await;
     let memory_info = app_state.system_status.memory.clone();
-    Ok(Json(memory_info))
+    let headers = freshness_headers(&app_state.system_status, Section::Memory);
+    Ok((headers, Json(memory_info)))
 }
 
 pub async fn get_processes_info(
     State(state): State<Arc<RwLock<AppState>>>,
-) -> Result<Json<ProcessesInfo>, StatusCode> {
+    query: Result<Query<ProcessQuery>, QueryRejection>,
+) -> Result<(HeaderMap, Json<ProcessesInfo>), (StatusCode, String)> {
     debug!("Handling /processes request");
+    let Query(query) = query.map_err(|e| (StatusCode::BAD_REQUEST, e.body_text()))?;
     let app_state = state.read().await;
-    let processes_info = app_state.system_status.processes.clone();
-    Ok(Json(processes_info))
+    let processes_info = query
+        .apply(&app_state.system_status.processes.processes)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    let headers = freshness_headers(&app_state.system_status, Section::Processes);
+    Ok((headers, Json(processes_info)))
+}
+
+pub async fn get_process_tree(
+    State(state): State<Arc<RwLock<AppState>>>,
+) -> Result<Json<ProcessTree>, StatusCode> {
+    debug!("Handling /processes/tree request");
+    let app_state = state.read().await;
+    let processes = process_tree::build_tree(&app_state.system_status.processes.processes);
+    Ok(Json(ProcessTree { processes }))
+}
+
+pub async fn get_process_info(
+    State(state): State<Arc<RwLock<AppState>>>,
+    Path(pid): Path<u32>,
+) -> Result<Json<ProcessDetails>, (StatusCode, String)> {
+    debug!("Handling /processes/{} request", pid);
+    let (process, status_source) = {
+        let app_state = state.read().await;
+        let process = app_state
+            .system_status
+            .processes
+            .processes
+            .iter()
+            .find(|p| p.pid == Some(pid))
+            .cloned();
+        (process, Arc::clone(&app_state.status_source))
+    };
+    let process = process.ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            format!("No process with PID {}", pid),
+        )
+    })?;
+
+    let extended = match status_source.process_details(pid, process.start_time).await {
+        DetailsLookup::Found(extended) => extended,
+        DetailsLookup::Unavailable => ProcessExtendedInfo::default(),
+        DetailsLookup::PidReused { start_time } => {
+            return Err((
+                StatusCode::CONFLICT,
+                format!(
+                    "PID {} was reused by a process started at {} ticks after boot; the snapshot is out of date",
+                    pid, start_time
+                ),
+            ));
+        }
+    };
+    Ok(Json(ProcessDetails { process, extended }))
 }
 
 pub async fn get_ext_temp_info(
     State(state): State<Arc<RwLock<AppState>>>,
-) -> Result<Json<ExternalTemperatureInfo>, StatusCode> {
+) -> Result<(HeaderMap, Json<ExternalTemperatureInfo>), StatusCode> {
     debug!("Handling /ext_temp request");
     let app_state = state.read().await;
     let ext_temp_info = app_state.system_status.external_temperature.clone();
-    Ok(Json(ext_temp_info))
+    let headers = freshness_headers(&app_state.system_status, Section::ExternalTemperature);
+    Ok((headers, Json(ext_temp_info)))
+}
+
+pub async fn get_ext_sensor_info(
+    State(state): State<Arc<RwLock<AppState>>>,
+    Path(name): Path<String>,
+) -> Result<(HeaderMap, Json<ExternalSensorInfo>), (StatusCode, String)> {
+    debug!("Handling /ext_temp/{} request", name);
+    let app_state = state.read().await;
+    let sensor = app_state
+        .system_status
+        .external_temperature
+        .sensors
+        .iter()
+        .find(|sensor| sensor.name == name)
+        .cloned()
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                format!("No external sensor named '{}'", name),
+            )
+        })?;
+    let headers = freshness_headers(&app_state.system_status, Section::ExternalTemperature);
+    Ok((headers, Json(sensor)))
+}
+
+pub async fn get_system_info(
+    State(state): State<Arc<RwLock<AppState>>>,
+) -> Result<(HeaderMap, Json<SystemInfo>), StatusCode> {
+    debug!("Handling /system request");
+    let app_state = state.read().await;
+    let system_info = app_state.system_status.system.clone();
+    let headers = freshness_headers(&app_state.system_status, Section::System);
+    Ok((headers, Json(system_info)))
+}
+
+pub async fn get_disks_info(
+    State(state): State<Arc<RwLock<AppState>>>,
+) -> Result<(HeaderMap, Json<DisksInfo>), StatusCode> {
+    debug!("Handling /disks request");
+    let app_state = state.read().await;
+    let disks_info = app_state.system_status.disks.clone();
+    let headers = freshness_headers(&app_state.system_status, Section::Disks);
+    Ok((headers, Json(disks_info)))
+}
+
+pub async fn get_network_info(
+    State(state): State<Arc<RwLock<AppState>>>,
+) -> Result<(HeaderMap, Json<NetworkInfo>), StatusCode> {
+    debug!("Handling /network request");
+    let app_state = state.read().await;
+    let network_info = app_state.system_status.network.clone();
+    let headers = freshness_headers(&app_state.system_status, Section::Network);
+    Ok((headers, Json(network_info)))
+}
+
+pub async fn get_thermal_info(
+    State(state): State<Arc<RwLock<AppState>>>,
+) -> Result<(HeaderMap, Json<ThermalInfo>), StatusCode> {
+    debug!("Handling /thermal request");
+    let app_state = state.read().await;
+    let thermal_info = app_state.system_status.thermal.clone();
+    let headers = freshness_headers(&app_state.system_status, Section::Thermal);
+    Ok((headers, Json(thermal_info)))
 }
 
 // --- Control Handlers ---
@@ -87,6 +499,9 @@ fn map_control_error(e: ControlError) -> (StatusCode, String) {
 #[derive(Deserialize, Debug)]
 pub struct KillRequest {
     pid: u32,
+    // Also terminate all descendants, children before their parents
+    #[serde(default)]
+    tree: bool,
 }
 
 #[derive(Deserialize, Debug)]
@@ -95,19 +510,98 @@ pub struct SetGpioRequest {
     gpio_val: u8, // Value (0 for low, 1 for high)
 }
 
+// A descendant the controller failed to kill in a tree kill
+#[derive(Serialize, Debug)]
+pub struct KillFailure {
+    pid: u32,
+    error: String,
+}
+
+// Body of a 207 response to a tree kill: the root was killed, some of its
+// descendants were not
+#[derive(Serialize, Debug)]
+pub struct KillTreeResponse {
+    killed: Vec<u32>,
+    failed: Vec<KillFailure>,
+}
+
 pub async fn kill_process(
     State(state): State<Arc<RwLock<AppState>>>,
     Json(payload): Json<KillRequest>,
-) -> Result<StatusCode, (StatusCode, String)> {
+) -> Result<Response, (StatusCode, String)> {
     debug!(
         "Handling POST /control/process/kill with payload: {:?}",
         payload
     );
     let app_state = state.read().await;
-    match app_state.controller_client.kill_process(payload.pid).await {
-        Ok(_) => Ok(StatusCode::OK),
-        Err(e) => Err(map_control_error(e)),
+    if !payload.tree {
+        return match app_state.controller_client.kill_process(payload.pid).await {
+            Ok(_) => Ok(StatusCode::OK.into_response()),
+            Err(e) => Err(map_control_error(e)),
+        };
+    }
+
+    let processes = &app_state.system_status.processes.processes;
+    let pids = process_tree::subtree_leaf_first(processes, payload.pid);
+    let snapshot: HashMap<u32, (Option<u32>, Option<u64>)> = processes
+        .iter()
+        .filter_map(|p| Some((p.pid?, (p.ppid, p.start_time))))
+        .collect();
+    let controller_client = Arc::clone(&app_state.controller_client);
+    let status_source = Arc::clone(&app_state.status_source);
+    drop(app_state);
+
+    // A descendant that cannot be killed does not stop the others; the root
+    // comes last
+    debug!("Killing process tree of PID {}: {:?}", payload.pid, pids);
+    let mut killed = Vec::new();
+    let mut failed: Vec<KillFailure> = Vec::new();
+    for pid in pids {
+        // The snapshot can be a refresh old, and a PID whose process exited
+        // since may belong to an unrelated one by now
+        let result = match (
+            status_source.process_identity(pid).await,
+            snapshot.get(&pid),
+        ) {
+            (None, _) => Err((StatusCode::CONFLICT, "No longer running".to_string())),
+            (Some(identity), Some(&(ppid, start_time)))
+                if ppid.is_some_and(|ppid| ppid != identity.ppid)
+                    || start_time.is_some_and(|start| start != identity.start_time) =>
+            {
+                Err((
+                    StatusCode::CONFLICT,
+                    "The PID now belongs to a different process".to_string(),
+                ))
+            }
+            _ => controller_client
+                .kill_process(pid)
+                .await
+                .map_err(map_control_error),
+        };
+        match result {
+            Ok(_) => killed.push(pid),
+            Err((status, message)) if pid == payload.pid => {
+                let mut message = format!("Killing PID {}: {}", pid, message);
+                if !failed.is_empty() {
+                    let pids: Vec<String> = failed
+                        .iter()
+                        .map(|failure| failure.pid.to_string())
+                        .collect();
+                    message.push_str(&format!("; also failed: PIDs {}", pids.join(", ")));
+                }
+                return Err((status, message));
+            }
+            Err((_, error)) => failed.push(KillFailure { pid, error }),
+        }
+    }
+    if failed.is_empty() {
+        return Ok(StatusCode::OK.into_response());
     }
+    Ok((
+        StatusCode::MULTI_STATUS,
+        Json(KillTreeResponse { killed, failed }),
+    )
+        .into_response())
 }
 
 pub async fn set_gpio(
@@ -0,0 +1,472 @@
+use crate::models::{
+    CpuStat, Freshness, HistoryBucket, HistoryResponse, MemoryInfo, Section, SystemStatus,
+};
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    time::Duration,
+};
+use thiserror::Error;
+
+// Sections with a history. Only their numeric values are kept, not whole
+// snapshots: a process list per refresh would not fit a Pi's memory.
+pub const HISTORY_SECTIONS: [Section; 4] = [
+    Section::Cpu,
+    Section::Memory,
+    Section::ExternalTemperature,
+    Section::Thermal,
+];
+
+// Bucket count when the query gives no step
+const DEFAULT_BUCKETS: u64 = 300;
+const MAX_BUCKETS: u64 = 10_000;
+
+#[derive(Error, Debug)]
+pub enum HistoryError {
+    #[error("No history is kept for section '{0}'")]
+    NoHistory(Section),
+    #[error("'from' ({from}) is after 'to' ({to})")]
+    InvalidRange { from: u64, to: u64 },
+    #[error("'step' must be at least 1 ms")]
+    ZeroStep,
+    #[error("Range and step give {0} buckets, at most {MAX_BUCKETS} are allowed")]
+    TooManyBuckets(u64),
+    #[error("Reading the metric store failed: {0}")]
+    Store(String),
+}
+
+// Query string of GET /history/{section}, times in Unix epoch milliseconds,
+// e.g. ?from=1717171000000&to=1717174600000&step=60000&series=usage_percent
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct HistoryQuery {
+    from: Option<u64>,
+    to: Option<u64>,
+    // Bucket width; by default the range is split into 300 buckets
+    step: Option<u64>,
+    // Comma-separated series names; all series when absent
+    series: Option<String>,
+}
+
+#[derive(Debug)]
+struct Sample {
+    at: u64,
+    // Index into `SectionHistory::names`, and the value
+    values: Vec<(u32, f64)>,
+}
+
+#[derive(Debug, Default)]
+struct SectionHistory {
+    // Series names, stored once instead of with every sample
+    names: Vec<String>,
+    index: HashMap<String, u32>,
+    samples: VecDeque<Sample>,
+}
+
+impl SectionHistory {
+    fn name_index(&mut self, name: String) -> u32 {
+        if let Some(&index) = self.index.get(&name) {
+            return index;
+        }
+        let index = self.names.len() as u32;
+        self.names.push(name.clone());
+        self.index.insert(name, index);
+        index
+    }
+}
+
+pub fn is_fresh(status: &SystemStatus, section: Section) -> bool {
+    status
+        .freshness
+        .get(&section)
+        .is_some_and(|entry| entry.state == Freshness::Fresh)
+}
+
+// Widens a reading without the noise of its binary representation: 21.1_f32
+// becomes 21.1, not 21.100000381469727.
+pub fn from_f32(value: f32) -> f64 {
+    value.to_string().parse().unwrap_or(value as f64)
+}
+
+// Series of a `CpuStat`, of the whole CPU and of each core: the jiffy
+// counters and the shares derived from them.
+fn cpu_stat_series(stat: &CpuStat) -> [(&'static str, Option<f64>); 15] {
+    let counter = |value: Option<u64>| value.map(|value| value as f64);
+    let percent = |value: Option<f32>| value.map(from_f32);
+    [
+        ("user_norm", counter(stat.user_norm)),
+        ("user_nice", counter(stat.user_nice)),
+        ("kernel", counter(stat.kernel)),
+        ("idle", counter(stat.idle)),
+        ("iowait", counter(stat.iowait)),
+        ("irq", counter(stat.irq)),
+        ("soft_irq", counter(stat.soft_irq)),
+        ("usage_percent", percent(stat.usage_percent)),
+        ("user_percent", percent(stat.user_percent)),
+        ("nice_percent", percent(stat.nice_percent)),
+        ("kernel_percent", percent(stat.kernel_percent)),
+        ("idle_percent", percent(stat.idle_percent)),
+        ("iowait_percent", percent(stat.iowait_percent)),
+        ("irq_percent", percent(stat.irq_percent)),
+        ("soft_irq_percent", percent(stat.soft_irq_percent)),
+    ]
+}
+
+fn memory_series(memory: &MemoryInfo) -> [(&'static str, Option<f64>); 12] {
+    let kb = |value: Option<u64>| value.map(|value| value as f64);
+    [
+        ("total", kb(memory.total)),
+        ("free", kb(memory.free)),
+        ("available", kb(memory.available)),
+        ("buffers", kb(memory.buffers)),
+        ("cached", kb(memory.cached)),
+        ("shared", kb(memory.shared)),
+        ("dirty", kb(memory.dirty)),
+        ("slab", kb(memory.slab)),
+        ("swap_total", kb(memory.swap_total)),
+        ("swap_free", kb(memory.swap_free)),
+        ("used_percent", memory.used_percent.map(from_f32)),
+        ("swap_used_percent", memory.swap_used_percent.map(from_f32)),
+    ]
+}
+
+// Every series of one section with its value, if the snapshot has one. The
+// whole-CPU and memory series are always there; cores, sensors and thermal
+// devices are those of the snapshot.
+fn section_series(status: &SystemStatus, section: Section) -> Vec<(String, Option<f64>)> {
+    let mut series = Vec::new();
+    match section {
+        Section::Cpu => {
+            let cpu = &status.cpu;
+            series.push(("temperature".to_string(), cpu.cpu_temperature.map(from_f32)));
+            let usage = cpu.cpu_usage.as_ref();
+            let full = usage
+                .and_then(|usage| usage.full.clone())
+                .unwrap_or_default();
+            for (name, value) in cpu_stat_series(&full) {
+                series.push((name.to_string(), value));
+            }
+            for core in usage
+                .and_then(|usage| usage.cores.as_ref())
+                .into_iter()
+                .flatten()
+            {
+                for (name, value) in cpu_stat_series(&core.stats) {
+                    series.push((format!("core{}.{}", core.core_id, name), value));
+                }
+            }
+        }
+        Section::Memory => {
+            for (name, value) in memory_series(&status.memory) {
+                series.push((name.to_string(), value));
+            }
+        }
+        Section::ExternalTemperature => {
+            for sensor in &status.external_temperature.sensors {
+                // A failed sensor holds its earlier values under the retain
+                // policy, which are not new samples
+                let read = sensor.error.is_none();
+                series.push((
+                    format!("{}.temperature", sensor.name),
+                    sensor.celsius().filter(|_| read).map(from_f32),
+                ));
+                series.push((
+                    format!("{}.humidity", sensor.name),
+                    sensor.humidity.filter(|_| read).map(from_f32),
+                ));
+            }
+        }
+        Section::Thermal => {
+            let thermal = &status.thermal;
+            for zone in &thermal.zones {
+                series.push((
+                    format!("{}.temperature", zone.name),
+                    zone.temperature.map(from_f32),
+                ));
+            }
+            for chip in &thermal.hwmon {
+                for temp in &chip.temperatures {
+                    series.push((
+                        format!("{}.{}", chip.name, temp.name),
+                        temp.temperature.map(from_f32),
+                    ));
+                }
+                for fan in &chip.fans {
+                    series.push((
+                        format!("{}.{}.rpm", chip.name, fan.name),
+                        fan.rpm.map(|rpm| rpm as f64),
+                    ));
+                }
+            }
+            for device in &thermal.cooling_devices {
+                series.push((
+                    format!("{}.state", device.name),
+                    device.cur_state.map(|state| state as f64),
+                ));
+            }
+        }
+        _ => {}
+    }
+    series
+}
+
+// Values of one section as (series name, value) pairs.
+pub fn series_values(status: &SystemStatus, section: Section) -> Vec<(String, f64)> {
+    section_series(status, section)
+        .into_iter()
+        .filter_map(|(name, value)| value.map(|value| (name, value)))
+        .collect()
+}
+
+// Names of every series of one section, with or without a value in the
+// snapshot.
+pub fn series_names(status: &SystemStatus, section: Section) -> Vec<String> {
+    section_series(status, section)
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Accumulator {
+    min: f32,
+    max: f32,
+    sum: f64,
+    count: u32,
+}
+
+impl Accumulator {
+    fn merge(&mut self, other: &Accumulator) {
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.sum += other.sum;
+        self.count += other.count;
+    }
+}
+
+// Buckets of every series of a query, filled from the in-memory history and
+// the persistent store.
+#[derive(Debug, Default)]
+pub struct Buckets {
+    series: BTreeMap<String, BTreeMap<u64, Accumulator>>,
+}
+
+impl Buckets {
+    pub fn add(&mut self, name: &str, start: u64, value: f32) {
+        self.add_rollup(name, start, value, value, value, 1);
+    }
+
+    // Adds an already aggregated set of `count` samples.
+    pub fn add_rollup(&mut self, name: &str, start: u64, min: f32, avg: f32, max: f32, count: u32) {
+        let incoming = Accumulator {
+            min,
+            max,
+            sum: avg as f64 * count as f64,
+            count,
+        };
+        if !self.series.contains_key(name) {
+            self.series.insert(name.to_string(), BTreeMap::new());
+        }
+        let series = self.series.get_mut(name).expect("inserted above");
+        series
+            .entry(start)
+            .and_modify(|acc| acc.merge(&incoming))
+            .or_insert(incoming);
+    }
+
+    pub fn into_response(self, section: Section, range: &HistoryRange) -> HistoryResponse {
+        let series = self
+            .series
+            .into_iter()
+            .map(|(name, buckets)| {
+                let buckets = buckets
+                    .into_iter()
+                    .map(|(start, acc)| HistoryBucket {
+                        start,
+                        min: acc.min,
+                        avg: (acc.sum / acc.count as f64) as f32,
+                        max: acc.max,
+                        count: acc.count,
+                    })
+                    .collect();
+                (name, buckets)
+            })
+            .collect();
+        HistoryResponse {
+            section,
+            from: range.from,
+            to: range.to,
+            step: range.step,
+            series,
+        }
+    }
+}
+
+// A validated `HistoryQuery`
+#[derive(Debug, Clone)]
+pub struct HistoryRange {
+    pub from: u64,
+    pub to: u64,
+    pub step: u64,
+    wanted: Option<Vec<String>>,
+}
+
+impl HistoryRange {
+    pub fn wants(&self, name: &str) -> bool {
+        self.wanted
+            .as_ref()
+            .is_none_or(|wanted| wanted.iter().any(|w| w == name))
+    }
+
+    // The same range and buckets, ending at `to`
+    pub fn truncated(&self, to: u64) -> HistoryRange {
+        HistoryRange {
+            to: to.min(self.to),
+            ..self.clone()
+        }
+    }
+
+    pub fn bucket_start(&self, at: u64) -> u64 {
+        self.from + (at.saturating_sub(self.from)) / self.step * self.step
+    }
+}
+
+impl HistoryQuery {
+    // Fills in the defaults: the last `retention_ms` up to `now`, split into
+    // 300 buckets.
+    pub fn resolve(&self, now: u64, retention_ms: u64) -> Result<HistoryRange, HistoryError> {
+        let to = self.to.unwrap_or(now);
+        let from = self.from.unwrap_or(to.saturating_sub(retention_ms));
+        if from > to {
+            return Err(HistoryError::InvalidRange { from, to });
+        }
+        let step = match self.step {
+            Some(0) => return Err(HistoryError::ZeroStep),
+            Some(step) => step,
+            None => (to - from).div_ceil(DEFAULT_BUCKETS).max(1),
+        };
+        let buckets = (to - from) / step + 1;
+        if buckets > MAX_BUCKETS {
+            return Err(HistoryError::TooManyBuckets(buckets));
+        }
+        Ok(HistoryRange {
+            from,
+            to,
+            step,
+            wanted: self.series.as_deref().map(|list| {
+                list.split(',')
+                    .map(|name| name.trim().to_string())
+                    .collect()
+            }),
+        })
+    }
+}
+
+// Samples of one section copied out of the history, for GET /export
+#[derive(Debug, Default)]
+pub struct SectionSamples {
+    pub names: Vec<String>,
+    // Time and (index into `names`, value) pairs
+    pub samples: Vec<(u64, Vec<(u32, f64)>)>,
+}
+
+// Bounded per-section time series of past refreshes, oldest first.
+#[derive(Debug)]
+pub struct History {
+    retention_ms: u64,
+    max_samples: usize,
+    sections: BTreeMap<Section, SectionHistory>,
+}
+
+impl History {
+    pub fn new(retention: Duration, max_samples: usize) -> Self {
+        History {
+            retention_ms: retention.as_millis() as u64,
+            max_samples: max_samples.max(1),
+            sections: BTreeMap::new(),
+        }
+    }
+
+    // Appends the refreshed sections of `status` that hold fresh data. Stale
+    // or retained data would repeat an old value under a new time.
+    pub fn record(&mut self, status: &SystemStatus, sections: &[Section]) {
+        let at = status.collected_at;
+        for &section in sections {
+            if !HISTORY_SECTIONS.contains(&section) {
+                continue;
+            }
+            if !is_fresh(status, section) {
+                continue;
+            }
+
+            let history = self.sections.entry(section).or_default();
+            let values = series_values(status, section)
+                .into_iter()
+                .map(|(name, value)| (history.name_index(name), value))
+                .collect();
+            history.samples.push_back(Sample { at, values });
+
+            let oldest_kept = at.saturating_sub(self.retention_ms);
+            while history
+                .samples
+                .front()
+                .is_some_and(|sample| sample.at < oldest_kept)
+                || history.samples.len() > self.max_samples
+            {
+                history.samples.pop_front();
+            }
+        }
+    }
+
+    // When the oldest sample of `section` was taken. Older ranges are
+    // answered from the persistent store, if there is one.
+    pub fn oldest(&self, section: Section) -> Option<u64> {
+        self.sections
+            .get(&section)?
+            .samples
+            .front()
+            .map(|sample| sample.at)
+    }
+
+    pub fn retention_ms(&self) -> u64 {
+        self.retention_ms
+    }
+
+    // Adds the samples of `section` within the range to `buckets`.
+    pub fn accumulate(&self, section: Section, range: &HistoryRange, buckets: &mut Buckets) {
+        let Some(history) = self.sections.get(&section) else {
+            return;
+        };
+        let first = history
+            .samples
+            .partition_point(|sample| sample.at < range.from);
+        for sample in history.samples.range(first..) {
+            if sample.at > range.to {
+                break;
+            }
+            for &(index, value) in &sample.values {
+                let name = &history.names[index as usize];
+                if range.wants(name) {
+                    buckets.add(name, range.bucket_start(sample.at), value as f32);
+                }
+            }
+        }
+    }
+
+    // Copies the samples of `section` taken within [from, to].
+    pub fn samples(&self, section: Section, from: u64, to: u64) -> SectionSamples {
+        let Some(history) = self.sections.get(&section) else {
+            return SectionSamples::default();
+        };
+        let first = history.samples.partition_point(|sample| sample.at < from);
+        SectionSamples {
+            names: history.names.clone(),
+            samples: history
+                .samples
+                .range(first..)
+                .take_while(|sample| sample.at <= to)
+                .map(|sample| (sample.at, sample.values.clone()))
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,27 @@
+use crate::data_source::ParseError;
+use std::{io, path::Path};
+use tokio::fs;
+use tracing::debug;
+
+// Reading of the procfs and sysfs files that the native collector and the
+// disks, network and thermal sections use.
+
+pub async fn read_file(file_path: &Path) -> Result<String, ParseError> {
+    fs::read_to_string(file_path)
+        .await
+        .map_err(|e| ParseError::Io(file_path.to_string_lossy().into_owned(), e))
+}
+
+// Reads a single-value sysfs attribute, trimmed. Many attributes only exist
+// for some devices, so a missing one is not logged.
+pub async fn read_attribute(path: &Path) -> Option<String> {
+    match fs::read_to_string(path).await {
+        Ok(value) => Some(value.trim().to_string()),
+        Err(e) => {
+            if e.kind() != io::ErrorKind::NotFound {
+                debug!("{}: {}", path.display(), e);
+            }
+            None
+        }
+    }
+}
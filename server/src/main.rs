@@ -1,9 +1,27 @@
+mod clock;
 mod config;
 mod controller;
 mod data_source;
+mod disks;
+mod export;
 mod handlers;
+mod history;
+mod kernel_fs;
+mod metrics;
 mod models;
+mod network;
+mod process_query;
+mod process_tree;
+mod procfs;
+mod source;
+mod status_json;
+mod status_stream;
+mod store;
 mod terminal;
+mod thermal;
+mod updater;
+mod usage;
+mod watcher;
 
 use axum::{
     extract::ws::WebSocketUpgrade,
@@ -11,18 +29,46 @@ use axum::{
     routing::{get, post},
     Router,
 };
-use config::Settings;
+use config::{DataSourceKind, ExtSensorList, Settings};
 use controller::ControllerClient;
-use data_source::read_status_files;
-use models::SystemStatus;
-use std::{process, sync::Arc, time::Duration};
-use tokio::sync::RwLock;
+use history::History;
+use models::{ParseDiagnostics, ParseErrorRecord, Section, SystemStatus};
+use source::StatusSource;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    process,
+    sync::Arc,
+    time::Duration,
+};
+use store::{Store, StoreRetention};
+use tokio::sync::{watch, RwLock};
 use tracing::{error, info, warn};
 use tracing_subscriber::{fmt, prelude::*, EnvFilter};
+use updater::Updater;
+use usage::Counters;
 
 #[derive(Debug)]
 pub struct AppState {
     pub system_status: SystemStatus,
+    // Sections collected at least once since the server started
+    pub collected_sections: BTreeSet<Section>,
+    // Most recent read failure per source file
+    pub parse_errors: Vec<ParseErrorRecord>,
+    // Line-level findings of the latest read of each status file
+    pub parse_diagnostics: ParseDiagnostics,
+    // Past values of the sections with a history, for GET /history
+    pub history: History,
+    // On-disk history beyond the in-memory retention, when DATA_DIR is set
+    pub store: Option<Arc<Store>>,
+    // Cardinality cap of the per-process series of GET /metrics
+    pub metrics_max_processes: usize,
+    // Per-core CPU jiffies as counters for GET /metrics, see `CpuUsageTracker`
+    pub cpu_totals: BTreeMap<u32, Counters>,
+    // Latest snapshot for /status/ws clients, published after each refresh
+    // while any client is connected
+    pub status_updates: watch::Sender<Arc<SystemStatus>>,
+    pub ws_min_interval: Duration,
+    pub status_source: Arc<dyn StatusSource>,
     pub controller_client: Arc<ControllerClient>,
 }
 
@@ -40,16 +86,28 @@ async fn main() {
         .init();
 
     info!("Starting system status API...");
+    match settings.data_source {
+        DataSourceKind::StatusFiles => info!(
+            "Data source files: CPU='{}', RAM/Usage='{}', Proc='{}', ExtTemp='{}', System='{}'",
+            settings.cpu_file.display(),
+            settings.ram_file.display(),
+            settings.proc_file.display(),
+            ExtSensorList(settings.ext_sensors.clone()),
+            settings.system_file.display()
+        ),
+        DataSourceKind::Procfs => info!(
+            "Data source: native collector, procfs='{}', sysfs='{}'",
+            settings.procfs_root.display(),
+            settings.sysfs_root.display()
+        ),
+    }
     info!(
-        "Data source files: CPU='{}', RAM/Usage='{}', Proc='{}', ExtTemp='{}'",
-        settings.cpu_file.display(),
-        settings.ram_file.display(),
-        settings.proc_file.display(),
-        settings.ext_temp_file.display()
+        "Data refresh: mode '{}', interval {} seconds",
+        settings.refresh_mode, settings.update_interval_secs
     );
     info!(
-        "Data refresh interval: {} seconds",
-        settings.update_interval_secs
+        "Read errors: policy '{}', data older than {} seconds is stale, strict parsing {}",
+        settings.parse_error_policy, settings.max_data_age_secs, settings.strict_parsing
     );
     info!(
         "Controller: host='{}', port={}, key=0x{:X}",
@@ -79,45 +137,104 @@ async fn main() {
         ),
     }
 
+    let status_source = source::build_source(&settings);
+
+    let store = settings.data_dir.as_ref().map(|data_dir| {
+        let retention = StoreRetention {
+            raw: Duration::from_secs(settings.store_raw_retention_secs),
+            minute: Duration::from_secs(settings.store_minute_retention_secs),
+            hour: Duration::from_secs(settings.store_hour_retention_secs),
+        };
+        match Store::open(data_dir, retention, settings.store_top_processes) {
+            Ok(store) => {
+                info!(
+                    "Metric store: '{}', flushed every {} seconds",
+                    store.path().display(),
+                    settings.store_flush_secs
+                );
+                Arc::new(store)
+            }
+            Err(e) => {
+                error!(
+                    "Fatal: Failed to open metric store in '{}': {}",
+                    data_dir.display(),
+                    e
+                );
+                process::exit(1);
+            }
+        }
+    });
+
     // --- Create Shared State ---
     let shared_state = Arc::new(RwLock::new(AppState {
         system_status: SystemStatus::default(),
+        collected_sections: BTreeSet::new(),
+        parse_errors: Vec::new(),
+        parse_diagnostics: ParseDiagnostics {
+            strict: settings.strict_parsing,
+            files: Vec::new(),
+        },
+        history: History::new(
+            Duration::from_secs(settings.history_retention_secs),
+            settings.history_max_samples,
+        ),
+        store: store.clone(),
+        metrics_max_processes: settings.metrics_max_processes,
+        cpu_totals: BTreeMap::new(),
+        status_updates: watch::Sender::new(Arc::new(SystemStatus::default())),
+        ws_min_interval: Duration::from_millis(settings.ws_min_interval_ms),
+        status_source: Arc::clone(&status_source),
         controller_client: Arc::clone(&controller_client),
     }));
 
     // --- Background Task Periodic Updates ---
     let state_clone_for_updater = Arc::clone(&shared_state);
-    let settings_clone_for_updater = settings.clone();
-    tokio::spawn(async move {
-        let mut interval = tokio::time::interval(Duration::from_secs(
-            settings_clone_for_updater.update_interval_secs,
+    let capabilities = status_source.capabilities();
+    info!(
+        "Status source '{}' provides: cpu={}, memory={}, processes={}, ext_temp={}, system={}, disks={}, network={}, thermal={}",
+        status_source.name(),
+        capabilities.cpu,
+        capabilities.memory,
+        capabilities.processes,
+        capabilities.external_temperature,
+        capabilities.system,
+        capabilities.disks,
+        capabilities.network,
+        capabilities.thermal
+    );
+    tokio::spawn(Updater::new(status_source, &settings).run(state_clone_for_updater));
+    tokio::spawn(controller::run_controller_probe(Arc::clone(
+        &controller_client,
+    )));
+    if let Some(store) = store {
+        tokio::spawn(store::run_store_maintenance(
+            Arc::clone(&store),
+            Duration::from_secs(settings.store_flush_secs),
         ));
-        loop {
-            interval.tick().await;
-            info!("Background task: Updating system status from files...");
-
-            let new_status = read_status_files(
-                &settings_clone_for_updater.cpu_file,
-                &settings_clone_for_updater.ram_file,
-                &settings_clone_for_updater.proc_file,
-                &settings_clone_for_updater.ext_temp_file,
-            )
-            .await;
-
-            {
-                let mut state_guard = state_clone_for_updater.write().await;
-                state_guard.system_status = new_status;
-            }
-            info!("Background task: System status update complete.");
-        }
-    });
+        tokio::spawn(store::flush_on_shutdown(store));
+    }
 
     // --- Setup Axum Router ---
     let app = Router::new()
+        .route("/status", get(handlers::get_status))
+        .route("/status/ws", get(handlers::get_status_ws))
+        .route("/health", get(handlers::get_health))
+        .route("/diagnostics/errors", get(handlers::get_parse_errors))
+        .route("/diagnostics/parse", get(handlers::get_parse_diagnostics))
         .route("/cpu", get(handlers::get_cpu_info))
         .route("/memory", get(handlers::get_memory_info))
         .route("/processes", get(handlers::get_processes_info))
+        .route("/processes/tree", get(handlers::get_process_tree))
+        .route("/processes/:pid", get(handlers::get_process_info))
         .route("/ext_temp", get(handlers::get_ext_temp_info))
+        .route("/ext_temp/:name", get(handlers::get_ext_sensor_info))
+        .route("/system", get(handlers::get_system_info))
+        .route("/disks", get(handlers::get_disks_info))
+        .route("/network", get(handlers::get_network_info))
+        .route("/thermal", get(handlers::get_thermal_info))
+        .route("/history/:section", get(handlers::get_history))
+        .route("/export", get(handlers::get_export))
+        .route("/metrics", get(handlers::get_metrics))
         .route("/control/ping", post(handlers::ping_controller))
         .route("/control/process/kill", post(handlers::kill_process))
         .route("/control/gpio/set", post(handlers::set_gpio))
@@ -0,0 +1,433 @@
+use crate::controller::ControllerContact;
+use crate::history::from_f32;
+use crate::models::{Freshness, ParseErrorRecord, SystemStatus};
+use crate::usage::Counters;
+use std::{collections::BTreeMap, fmt::Write};
+
+// GET /metrics in the Prometheus text exposition format, or in OpenMetrics
+// when the scraper asks for it. Built from the latest snapshot, so a scrape
+// never triggers a refresh.
+
+const PREFIX: &str = "rpi_";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricsFormat {
+    Prometheus,
+    OpenMetrics,
+}
+
+impl MetricsFormat {
+    // OpenMetrics when the Accept header lists it, as Prometheus' does
+    pub fn from_accept(accept: Option<&str>) -> Self {
+        match accept {
+            Some(accept) if accept.contains("application/openmetrics-text") => {
+                MetricsFormat::OpenMetrics
+            }
+            _ => MetricsFormat::Prometheus,
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            MetricsFormat::Prometheus => "text/plain; version=0.0.4; charset=utf-8",
+            MetricsFormat::OpenMetrics => {
+                "application/openmetrics-text; version=1.0.0; charset=utf-8"
+            }
+        }
+    }
+}
+
+// Snapshot data the metrics are rendered from
+pub struct MetricsInput<'a> {
+    pub status: &'a SystemStatus,
+    // Per-core jiffies in `Counters` order, totals even for interval sources
+    pub cpu_totals: &'a BTreeMap<u32, Counters>,
+    pub parse_errors: &'a [ParseErrorRecord],
+    pub controller: Option<ControllerContact>,
+    // Processes exposed with their own series, largest RSS first
+    pub max_processes: usize,
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+struct MetricsWriter {
+    format: MetricsFormat,
+    out: String,
+    // HELP and TYPE of the current family, written before its first sample
+    // so families without samples are left out
+    header: Option<String>,
+}
+
+impl MetricsWriter {
+    fn new(format: MetricsFormat) -> Self {
+        MetricsWriter {
+            format,
+            out: String::new(),
+            header: None,
+        }
+    }
+
+    // Starts a family. Counter names end in "_total", which OpenMetrics
+    // leaves out of the family name.
+    fn family(&mut self, name: &str, kind: &str, help: &str) {
+        let family = match (self.format, kind) {
+            (MetricsFormat::OpenMetrics, "counter") => name.trim_end_matches("_total"),
+            _ => name,
+        };
+        self.header = Some(format!(
+            "# HELP {prefix}{family} {help}\n# TYPE {prefix}{family} {kind}\n",
+            prefix = PREFIX
+        ));
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        if let Some(header) = self.header.take() {
+            self.out.push_str(&header);
+        }
+        self.out.push_str(PREFIX);
+        self.out.push_str(name);
+        if !labels.is_empty() {
+            self.out.push('{');
+            for (i, (label, value)) in labels.iter().enumerate() {
+                if i > 0 {
+                    self.out.push(',');
+                }
+                let _ = write!(self.out, "{}=\"{}\"", label, escape_label(value));
+            }
+            self.out.push('}');
+        }
+        let _ = writeln!(self.out, " {}", format_value(value));
+    }
+
+    fn gauge(&mut self, name: &str, help: &str, value: Option<f64>) {
+        self.family(name, "gauge", help);
+        if let Some(value) = value {
+            self.sample(name, &[], value);
+        }
+    }
+
+    fn finish(mut self) -> String {
+        if self.format == MetricsFormat::OpenMetrics {
+            self.out.push_str("# EOF\n");
+        }
+        self.out
+    }
+}
+
+// Jiffies per second of the /proc/stat counters
+fn clock_ticks_per_second() -> f64 {
+    // SAFETY: sysconf only reads a system constant.
+    let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if ticks > 0 {
+        ticks as f64
+    } else {
+        100.0
+    }
+}
+
+fn write_cpu(writer: &mut MetricsWriter, status: &SystemStatus, totals: &BTreeMap<u32, Counters>) {
+    let cpu = &status.cpu;
+    let ticks = clock_ticks_per_second();
+    writer.family(
+        "cpu_seconds_total",
+        "counter",
+        "Time each core spent in each mode.",
+    );
+    // Same order as `Counters`
+    let modes = ["user", "nice", "system", "idle", "iowait", "irq", "softirq"];
+    for (core_id, counters) in totals {
+        let id = core_id.to_string();
+        for (mode, value) in modes.iter().zip(counters) {
+            writer.sample(
+                "cpu_seconds_total",
+                &[("cpu", &id), ("mode", mode)],
+                *value as f64 / ticks,
+            );
+        }
+    }
+
+    let full = cpu.cpu_usage.as_ref().and_then(|usage| usage.full.as_ref());
+    writer.gauge(
+        "cpu_usage_percent",
+        "Share of the last refresh interval all cores were busy.",
+        full.and_then(|full| full.usage_percent).map(from_f32),
+    );
+    writer.gauge(
+        "cpu_temperature_celsius",
+        "CPU temperature.",
+        cpu.cpu_temperature.map(from_f32),
+    );
+}
+
+fn write_memory(writer: &mut MetricsWriter, status: &SystemStatus) {
+    let memory = &status.memory;
+    let fields = [
+        ("memory_total_bytes", "Total usable memory.", memory.total),
+        ("memory_free_bytes", "Unused memory.", memory.free),
+        (
+            "memory_available_bytes",
+            "Memory available for new processes without swapping.",
+            memory.available,
+        ),
+        (
+            "memory_buffers_bytes",
+            "Block device buffers.",
+            memory.buffers,
+        ),
+        ("memory_cached_bytes", "Page cache.", memory.cached),
+        (
+            "memory_shared_bytes",
+            "Shared memory and tmpfs.",
+            memory.shared,
+        ),
+        (
+            "memory_dirty_bytes",
+            "Memory waiting to be written back.",
+            memory.dirty,
+        ),
+        ("memory_slab_bytes", "Kernel data structures.", memory.slab),
+        (
+            "memory_swap_total_bytes",
+            "Total swap space.",
+            memory.swap_total,
+        ),
+        (
+            "memory_swap_free_bytes",
+            "Unused swap space.",
+            memory.swap_free,
+        ),
+    ];
+    for (name, help, kb) in fields {
+        writer.gauge(name, help, kb.map(|kb| kb as f64 * 1024.0));
+    }
+}
+
+fn write_temperatures(writer: &mut MetricsWriter, status: &SystemStatus) {
+    let sensors = &status.external_temperature.sensors;
+    writer.family(
+        "external_temperature_celsius",
+        "gauge",
+        "Temperature of an external sensor.",
+    );
+    for sensor in sensors {
+        if let Some(temperature) = sensor.celsius() {
+            writer.sample(
+                "external_temperature_celsius",
+                &[("sensor", &sensor.name)],
+                from_f32(temperature),
+            );
+        }
+    }
+    writer.family(
+        "external_humidity_percent",
+        "gauge",
+        "Relative humidity of an external sensor.",
+    );
+    for sensor in sensors {
+        if let Some(humidity) = sensor.humidity {
+            writer.sample(
+                "external_humidity_percent",
+                &[("sensor", &sensor.name)],
+                from_f32(humidity),
+            );
+        }
+    }
+
+    let thermal = &status.thermal;
+    writer.family(
+        "thermal_zone_temperature_celsius",
+        "gauge",
+        "Temperature of a kernel thermal zone.",
+    );
+    for zone in &thermal.zones {
+        if let Some(temperature) = zone.temperature {
+            let zone_type = zone.zone_type.as_deref().unwrap_or_default();
+            writer.sample(
+                "thermal_zone_temperature_celsius",
+                &[("zone", &zone.name), ("type", zone_type)],
+                from_f32(temperature),
+            );
+        }
+    }
+    writer.family(
+        "hwmon_temperature_celsius",
+        "gauge",
+        "Temperature of a hwmon sensor.",
+    );
+    for chip in &thermal.hwmon {
+        let device = chip.device_name.as_deref().unwrap_or_default();
+        for temp in &chip.temperatures {
+            if let Some(temperature) = temp.temperature {
+                writer.sample(
+                    "hwmon_temperature_celsius",
+                    &[
+                        ("chip", &chip.name),
+                        ("device", device),
+                        ("sensor", &temp.name),
+                        ("label", temp.label.as_deref().unwrap_or_default()),
+                    ],
+                    from_f32(temperature),
+                );
+            }
+        }
+    }
+    writer.family("hwmon_fan_rpm", "gauge", "Fan speed of a hwmon fan.");
+    for chip in &thermal.hwmon {
+        let device = chip.device_name.as_deref().unwrap_or_default();
+        for fan in &chip.fans {
+            if let Some(rpm) = fan.rpm {
+                writer.sample(
+                    "hwmon_fan_rpm",
+                    &[
+                        ("chip", &chip.name),
+                        ("device", device),
+                        ("fan", &fan.name),
+                        ("label", fan.label.as_deref().unwrap_or_default()),
+                    ],
+                    rpm as f64,
+                );
+            }
+        }
+    }
+    writer.family(
+        "cooling_device_state",
+        "gauge",
+        "Current state of a cooling device, from 0 to its max state.",
+    );
+    for device in &thermal.cooling_devices {
+        if let Some(state) = device.cur_state {
+            let device_type = device.device_type.as_deref().unwrap_or_default();
+            writer.sample(
+                "cooling_device_state",
+                &[("device", &device.name), ("type", device_type)],
+                state as f64,
+            );
+        }
+    }
+}
+
+fn write_processes(writer: &mut MetricsWriter, status: &SystemStatus, max_processes: usize) {
+    let processes = &status.processes.processes;
+    writer.gauge(
+        "processes",
+        "Number of processes.",
+        Some(processes.len() as f64),
+    );
+
+    // One series per process would grow without bound with short-lived
+    // commands, so only the largest ones are exposed
+    let mut largest: Vec<_> = processes.iter().filter(|p| p.pid.is_some()).collect();
+    largest.sort_by_key(|p| std::cmp::Reverse(p.memory_rss.unwrap_or(0)));
+    largest.truncate(max_processes);
+
+    writer.family(
+        "process_resident_memory_bytes",
+        "gauge",
+        "Resident memory of the processes with the most resident memory.",
+    );
+    for process in &largest {
+        if let (Some(pid), Some(rss)) = (process.pid, process.memory_rss) {
+            let name = process.name.as_deref().unwrap_or_default();
+            writer.sample(
+                "process_resident_memory_bytes",
+                &[("pid", &pid.to_string()), ("name", name)],
+                rss as f64 * 1024.0,
+            );
+        }
+    }
+    writer.family(
+        "process_threads",
+        "gauge",
+        "Threads of the processes with the most resident memory.",
+    );
+    for process in &largest {
+        if let (Some(pid), Some(threads)) = (process.pid, process.threads) {
+            let name = process.name.as_deref().unwrap_or_default();
+            writer.sample(
+                "process_threads",
+                &[("pid", &pid.to_string()), ("name", name)],
+                threads as f64,
+            );
+        }
+    }
+}
+
+fn write_server(writer: &mut MetricsWriter, input: &MetricsInput) {
+    let status = input.status;
+    if let Some(contact) = input.controller {
+        writer.gauge(
+            "controller_up",
+            "Whether the controller answered the most recent command or ping.",
+            Some(if contact.reachable { 1.0 } else { 0.0 }),
+        );
+        writer.gauge(
+            "controller_last_contact_timestamp_seconds",
+            "When the controller was last contacted.",
+            Some(contact.at as f64 / 1000.0),
+        );
+    }
+
+    writer.family(
+        "refreshes_total",
+        "counter",
+        "Refreshes of the status data since the server started.",
+    );
+    writer.sample("refreshes_total", &[], status.seq as f64);
+    writer.gauge(
+        "last_refresh_timestamp_seconds",
+        "When the status data was last refreshed.",
+        (status.collected_at > 0).then(|| status.collected_at as f64 / 1000.0),
+    );
+
+    writer.family(
+        "section_fresh",
+        "gauge",
+        "Whether a section holds fresh data (1), or stale or no data (0).",
+    );
+    for (section, entry) in &status.freshness {
+        let fresh = entry.state == Freshness::Fresh;
+        writer.sample(
+            "section_fresh",
+            &[("section", section.as_str())],
+            if fresh { 1.0 } else { 0.0 },
+        );
+    }
+
+    writer.family(
+        "parse_errors_total",
+        "counter",
+        "Failed reads of a status file since the server started.",
+    );
+    for record in input.parse_errors {
+        writer.sample(
+            "parse_errors_total",
+            &[("section", record.section.as_str()), ("file", &record.file)],
+            record.error_count as f64,
+        );
+    }
+}
+
+pub fn render(input: &MetricsInput, format: MetricsFormat) -> String {
+    let mut writer = MetricsWriter::new(format);
+    write_cpu(&mut writer, input.status, input.cpu_totals);
+    write_memory(&mut writer, input.status);
+    write_temperatures(&mut writer, input.status);
+    write_processes(&mut writer, input.status, input.max_processes);
+    write_server(&mut writer, input);
+    writer.finish()
+}
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, str::FromStr};
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct CpuInfo {
@@ -32,6 +33,23 @@ pub struct CpuStat {
     pub irq: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub soft_irq: Option<u64>,
+    // Derived by the server from counter deltas, share of the interval in percent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage_percent: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_percent: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nice_percent: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kernel_percent: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub idle_percent: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iowait_percent: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub irq_percent: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub soft_irq_percent: Option<f32>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
@@ -49,11 +67,34 @@ pub struct MemoryInfo {
     pub free: Option<u64>, // Assuming kB
     #[serde(skip_serializing_if = "Option::is_none")]
     pub available: Option<u64>, // Assuming kB
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub buffers: Option<u64>, // kB
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cached: Option<u64>, // kB, page cache
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shared: Option<u64>, // kB, shmem and tmpfs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dirty: Option<u64>, // kB, waiting to be written back
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slab: Option<u64>, // kB, kernel data structures
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub swap_total: Option<u64>, // kB
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub swap_free: Option<u64>, // kB
+    // Derived by the server, share of `total` in use
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub used_percent: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub swap_used_percent: Option<f32>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct ProcessesInfo {
+    #[serde(default)]
     pub processes: Vec<ProcessInfo>,
+    // Number of matching processes before paging, set by GET /processes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<usize>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
@@ -67,6 +108,8 @@ pub struct ProcessInfo {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub state_description: Option<String>, // e.g., "sleeping"
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub ppid: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub user: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub group: Option<String>,
@@ -80,20 +123,559 @@ pub struct ProcessInfo {
     pub threads: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub utime: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_time: Option<u64>, // Clock ticks after boot
+    // Derived by the server by tracking the process across refreshes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_percent: Option<f32>, // 100 = one core fully busy
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_rss_rate: Option<f64>, // kB per second
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_seen: Option<u64>, // Unix epoch milliseconds
+}
+
+// Detail only some sources can provide, looked up on demand for one process
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ProcessExtendedInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cmdline: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub open_files: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ProcessDetails {
+    #[serde(flatten)]
+    pub process: ProcessInfo,
+    #[serde(flatten)]
+    pub extended: ProcessExtendedInfo,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ProcessTreeNode {
+    #[serde(flatten)]
+    pub process: ProcessInfo,
+    pub children: Vec<ProcessTreeNode>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ProcessTree {
+    // Processes whose parent is not in the list (e.g. init, kernel threads)
+    pub processes: Vec<ProcessTreeNode>,
+}
+
+// Unit a sensor reports its temperature in, set per sensor in the settings
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TemperatureUnit {
+    #[default]
+    #[serde(rename = "C")]
+    Celsius,
+    #[serde(rename = "F")]
+    Fahrenheit,
+    #[serde(rename = "K")]
+    Kelvin,
+}
+
+impl FromStr for TemperatureUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "c" | "celsius" => Ok(TemperatureUnit::Celsius),
+            "f" | "fahrenheit" => Ok(TemperatureUnit::Fahrenheit),
+            "k" | "kelvin" => Ok(TemperatureUnit::Kelvin),
+            other => Err(format!("unknown temperature unit '{}'", other)),
+        }
+    }
+}
+
+impl TemperatureUnit {
+    pub fn to_celsius(self, value: f32) -> f32 {
+        match self {
+            TemperatureUnit::Celsius => value,
+            TemperatureUnit::Fahrenheit => (value - 32.0) * 5.0 / 9.0,
+            TemperatureUnit::Kelvin => value - 273.15,
+        }
+    }
+}
+
+impl fmt::Display for TemperatureUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TemperatureUnit::Celsius => write!(f, "C"),
+            TemperatureUnit::Fahrenheit => write!(f, "F"),
+            TemperatureUnit::Kelvin => write!(f, "K"),
+        }
+    }
+}
+
+// One external sensor. Also the content of its status file, where name and
+// unit are absent and taken from the settings.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ExternalSensorInfo {
+    pub name: String,
+    pub unit: TemperatureUnit,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub humidity: Option<f32>, // Relative, in percent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_at: Option<u64>, // Unix epoch milliseconds, when the value was written
+    // Why the latest read failed; the values are then from an earlier read
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ExternalSensorInfo {
+    pub fn celsius(&self) -> Option<f32> {
+        self.temperature.map(|t| self.unit.to_celsius(t))
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct ExternalTemperatureInfo {
+    // Temperature of the first sensor in °C, for clients written before
+    // there could be several
     #[serde(skip_serializing_if = "Option::is_none")]
     pub temperature: Option<f32>,
+    pub sensors: Vec<ExternalSensorInfo>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SystemInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub load1: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub load5: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub load15: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub running_tasks: Option<u32>, // Runnable at the time of sampling
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_tasks: Option<u32>, // Processes and threads
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uptime_secs: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub boot_time: Option<u64>, // Unix epoch milliseconds
+}
+
+// One mounted filesystem as reported by statvfs. Sizes in kB.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct FilesystemInfo {
+    pub device: String,
+    pub mount_point: String,
+    pub fs_type: String,
+    pub read_only: bool,
+    pub total: u64,
+    pub used: u64,
+    pub available: u64, // For unprivileged users, excludes reserved blocks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub used_percent: Option<f32>, // Of used + available, as df reports it
+    pub inodes_total: u64,
+    pub inodes_used: u64,
+    pub inodes_free: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inodes_used_percent: Option<f32>,
+}
+
+// One block device from /proc/diskstats. Counters are cumulative since boot,
+// rates are derived by the server over the last refresh interval.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct BlockDeviceInfo {
+    pub name: String,
+    pub reads_completed: u64,
+    pub sectors_read: u64, // 512 bytes each
+    pub writes_completed: u64,
+    pub sectors_written: u64,
+    pub io_time_ms: u64, // Time spent with I/O in flight
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_bytes_per_sec: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub write_bytes_per_sec: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reads_per_sec: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub writes_per_sec: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub busy_percent: Option<f32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct DisksInfo {
+    pub filesystems: Vec<FilesystemInfo>,
+    pub devices: Vec<BlockDeviceInfo>,
+}
+
+// One network interface. Counters are cumulative since the interface came
+// up, rates are derived by the server over the last refresh interval.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct NetworkInterfaceInfo {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mac_address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub operstate: Option<String>, // "up", "down", "dormant", "unknown", ...
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub carrier: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speed_mbps: Option<u32>, // Not reported by most Wi-Fi drivers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mtu: Option<u32>,
+    pub addresses: Vec<String>, // "192.168.1.20/24", "fe80::1/64"
+    pub rx_bytes: u64,
+    pub rx_packets: u64,
+    pub rx_errors: u64,
+    pub rx_dropped: u64,
+    pub tx_bytes: u64,
+    pub tx_packets: u64,
+    pub tx_errors: u64,
+    pub tx_dropped: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rx_bytes_per_sec: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rx_packets_per_sec: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rx_errors_per_sec: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rx_dropped_per_sec: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tx_bytes_per_sec: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tx_packets_per_sec: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tx_errors_per_sec: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tx_dropped_per_sec: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct NetworkInfo {
+    pub interfaces: Vec<NetworkInterfaceInfo>,
+}
+
+// Temperatures in this group are in degrees Celsius, converted from the
+// millidegrees of sysfs.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct TripPoint {
+    pub trip_type: String, // "active", "passive", "hot" or "critical"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ThermalZoneInfo {
+    pub name: String, // "thermal_zone0"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub zone_type: Option<String>, // "cpu-thermal", "x86_pkg_temp", ...
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    pub trip_points: Vec<TripPoint>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct HwmonTemperature {
+    pub name: String, // "temp1"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub critical: Option<f32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct HwmonFan {
+    pub name: String, // "fan1"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rpm: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_rpm: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct HwmonDeviceInfo {
+    pub name: String, // "hwmon0"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device_name: Option<String>, // Driver supplied, e.g. "cpu_thermal", "pwmfan"
+    pub temperatures: Vec<HwmonTemperature>,
+    pub fans: Vec<HwmonFan>,
+}
+
+// Fans driven in steps (e.g. a GPIO fan) appear as cooling devices only
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct CoolingDeviceInfo {
+    pub name: String, // "cooling_device0"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device_type: Option<String>, // "gpio-fan", "pwm-fan", "Processor", ...
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cur_state: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_state: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ThermalInfo {
+    pub zones: Vec<ThermalZoneInfo>,
+    pub hwmon: Vec<HwmonDeviceInfo>,
+    pub cooling_devices: Vec<CoolingDeviceInfo>,
 }
 
 // --- Overall System Status ---
 // Holds all parsed data combined from the files
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct SystemStatus {
+    // Incremented by the updater on every refresh, starting at 1
+    pub seq: u64,
+    pub collected_at: u64, // Unix epoch milliseconds
     pub cpu: CpuInfo,
     pub memory: MemoryInfo,
     pub processes: ProcessesInfo,
     pub external_temperature: ExternalTemperatureInfo,
+    pub system: SystemInfo,
+    pub disks: DisksInfo,
+    pub network: NetworkInfo,
+    pub thermal: ThermalInfo,
+    // Per-section data age, filled by the source and completed by the updater
+    pub freshness: BTreeMap<Section, SectionFreshness>,
+}
+
+impl SystemStatus {
+    // Records that a source read `section` successfully. The updater fills in
+    // `collected_at` and downgrades old data to stale.
+    pub fn mark_collected(&mut self, section: Section, source_mtime: Option<u64>) {
+        self.freshness.insert(
+            section,
+            SectionFreshness {
+                state: Freshness::Fresh,
+                collected_at: None,
+                source_mtime,
+                consecutive_errors: None,
+            },
+        );
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Freshness {
+    // Read successfully and the source data is recent
+    Fresh,
+    // Read successfully but the producer has not updated it within the max age
+    Stale,
+    // Not available from the source this refresh
+    #[default]
+    Missing,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SectionFreshness {
+    pub state: Freshness,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collected_at: Option<u64>, // Unix epoch milliseconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_mtime: Option<u64>, // Unix epoch milliseconds, file based sources only
+    // Failed reads since the last successful one; the data shown is older
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub consecutive_errors: Option<u32>,
+}
+
+// The independently collected parts of `SystemStatus`
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Section {
+    Cpu,
+    Memory,
+    Processes,
+    ExternalTemperature,
+    System,
+    Disks,
+    Network,
+    Thermal,
+}
+
+impl Section {
+    pub const ALL: [Section; 8] = [
+        Section::Cpu,
+        Section::Memory,
+        Section::Processes,
+        Section::ExternalTemperature,
+        Section::System,
+        Section::Disks,
+        Section::Network,
+        Section::Thermal,
+    ];
+
+    // Matches the field name in `SystemStatus`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Section::Cpu => "cpu",
+            Section::Memory => "memory",
+            Section::Processes => "processes",
+            Section::ExternalTemperature => "external_temperature",
+            Section::System => "system",
+            Section::Disks => "disks",
+            Section::Network => "network",
+            Section::Thermal => "thermal",
+        }
+    }
+}
+
+impl FromStr for Section {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "cpu" => Ok(Section::Cpu),
+            "memory" => Ok(Section::Memory),
+            "processes" => Ok(Section::Processes),
+            // "ext_temp" matches the /ext_temp endpoint
+            "external_temperature" | "ext_temp" => Ok(Section::ExternalTemperature),
+            "system" => Ok(Section::System),
+            "disks" => Ok(Section::Disks),
+            "network" => Ok(Section::Network),
+            "thermal" => Ok(Section::Thermal),
+            other => Err(format!(
+                "Unknown section '{}', expected one of: cpu, memory, processes, ext_temp, system, disks, network, thermal",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// Parses a comma-separated section list such as "cpu,memory".
+pub fn parse_sections(list: &str) -> Result<Vec<Section>, String> {
+    list.split(',')
+        .filter(|s| !s.trim().is_empty())
+        .map(Section::from_str)
+        .collect()
+}
+
+// Body of GET /status: the selected sections of one snapshot
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct StatusResponse {
+    pub seq: u64,
+    pub collected_at: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu: Option<CpuInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory: Option<MemoryInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub processes: Option<ProcessesInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_temperature: Option<ExternalTemperatureInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system: Option<SystemInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disks: Option<DisksInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<NetworkInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thermal: Option<ThermalInfo>,
+    pub freshness: BTreeMap<Section, SectionFreshness>,
+}
+
+impl StatusResponse {
+    pub fn from_status(status: &SystemStatus, sections: &[Section]) -> Self {
+        let mut response = StatusResponse {
+            seq: status.seq,
+            collected_at: status.collected_at,
+            ..Default::default()
+        };
+        for section in sections {
+            response.freshness.insert(
+                *section,
+                status.freshness.get(section).cloned().unwrap_or_default(),
+            );
+            match section {
+                Section::Cpu => response.cpu = Some(status.cpu.clone()),
+                Section::Memory => response.memory = Some(status.memory.clone()),
+                Section::Processes => response.processes = Some(status.processes.clone()),
+                Section::ExternalTemperature => {
+                    response.external_temperature = Some(status.external_temperature.clone())
+                }
+                Section::System => response.system = Some(status.system.clone()),
+                Section::Disks => response.disks = Some(status.disks.clone()),
+                Section::Network => response.network = Some(status.network.clone()),
+                Section::Thermal => response.thermal = Some(status.thermal.clone()),
+            }
+        }
+        response
+    }
+}
+
+// Body of GET /health
+#[derive(Debug, Serialize, Clone)]
+pub struct HealthResponse {
+    // "ok" when every section the source provides is fresh, else "degraded"
+    pub status: String,
+    pub seq: u64,
+    pub collected_at: u64,
+    pub sections: BTreeMap<Section, SectionFreshness>,
+}
+
+// One bucket of a downsampled series in GET /history/{section}
+#[derive(Debug, Serialize, Clone)]
+pub struct HistoryBucket {
+    pub start: u64, // Unix epoch milliseconds
+    pub min: f32,
+    pub avg: f32,
+    pub max: f32,
+    pub count: u32, // Samples in the bucket
+}
+
+// Body of GET /history/{section}. Buckets without samples are left out.
+#[derive(Debug, Serialize, Clone)]
+pub struct HistoryResponse {
+    pub section: Section,
+    pub from: u64,
+    pub to: u64,
+    pub step: u64,
+    pub series: BTreeMap<String, Vec<HistoryBucket>>,
+}
+
+// Most recent failure to read one source file, served by GET /diagnostics/errors
+#[derive(Debug, Serialize, Clone)]
+pub struct ParseErrorRecord {
+    pub file: String,
+    pub section: Section,
+    pub message: String,
+    pub last_error_at: u64, // Unix epoch milliseconds
+    pub error_count: u64,   // Since server start
+    pub consecutive_errors: u32,
+}
+
+// A line of a status file the parser could not use
+#[derive(Debug, Serialize, Clone)]
+pub struct LineFinding {
+    pub line_number: usize, // 1-based, counted in the file as written
+    pub line: String,
+    pub reason: String,
+}
+
+// Findings from the latest read of one status file
+#[derive(Debug, Serialize, Clone)]
+pub struct FileFindings {
+    pub file: String,
+    pub section: Section,
+    pub collected_at: u64, // Unix epoch milliseconds
+    pub findings: Vec<LineFinding>,
+}
+
+// Served by GET /diagnostics/parse
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct ParseDiagnostics {
+    // Findings are only collected in strict parse mode
+    pub strict: bool,
+    pub files: Vec<FileFindings>,
 }
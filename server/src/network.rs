@@ -0,0 +1,171 @@
+use crate::data_source::ParseError;
+use crate::kernel_fs::{read_attribute, read_file};
+use crate::models::{NetworkInfo, NetworkInterfaceInfo, Section};
+use crate::source::Collection;
+use std::{
+    collections::HashMap,
+    ffi::CStr,
+    io,
+    net::{Ipv4Addr, Ipv6Addr},
+    path::Path,
+};
+use tracing::{error, warn};
+
+// Counters from <procfs>/net/dev, link state from <sysfs>/class/net and
+// addresses from getifaddrs.
+
+// Parses the interface lines of /proc/net/dev:
+// "  eth0: rx bytes packets errs drop fifo frame compressed multicast
+//          tx bytes packets errs drop fifo colls carrier compressed"
+fn parse_net_dev(content: &str, file_path: &Path) -> Vec<NetworkInterfaceInfo> {
+    let mut interfaces = Vec::new();
+    // Two header lines
+    for line in content.lines().skip(2) {
+        let Some((name, counters)) = line.split_once(':') else {
+            warn!(
+                "{}: skipping malformed line '{}'",
+                file_path.display(),
+                line
+            );
+            continue;
+        };
+        let values: Result<Vec<u64>, _> = counters.split_whitespace().map(str::parse).collect();
+        let values = match values {
+            Ok(values) if values.len() >= 16 => values,
+            _ => {
+                warn!(
+                    "{}: skipping line with missing or non-numeric counters '{}'",
+                    file_path.display(),
+                    line
+                );
+                continue;
+            }
+        };
+        interfaces.push(NetworkInterfaceInfo {
+            name: name.trim().to_string(),
+            rx_bytes: values[0],
+            rx_packets: values[1],
+            rx_errors: values[2],
+            rx_dropped: values[3],
+            tx_bytes: values[8],
+            tx_packets: values[9],
+            tx_errors: values[10],
+            tx_dropped: values[11],
+            ..Default::default()
+        });
+    }
+    interfaces
+}
+
+// Reads one attribute of <sysfs>/class/net/<name>. Some attributes cannot be
+// read in every state (carrier and speed of a down interface).
+async fn fill_link_state(interface: &mut NetworkInterfaceInfo, sysfs_root: &Path) {
+    let dir = sysfs_root.join("class/net").join(&interface.name);
+    interface.mac_address = read_attribute(&dir.join("address"))
+        .await
+        .filter(|mac| !mac.is_empty());
+    interface.operstate = read_attribute(&dir.join("operstate")).await;
+    interface.carrier = read_attribute(&dir.join("carrier"))
+        .await
+        .map(|carrier| carrier == "1");
+    // -1 when the link is down or the speed is unknown
+    interface.speed_mbps = read_attribute(&dir.join("speed"))
+        .await
+        .and_then(|speed| speed.parse().ok());
+    interface.mtu = read_attribute(&dir.join("mtu"))
+        .await
+        .and_then(|mtu| mtu.parse().ok());
+}
+
+fn prefix_len(netmask: &[u8]) -> u32 {
+    netmask.iter().map(|byte| byte.count_ones()).sum()
+}
+
+// Returns "address/prefix" strings per interface name.
+fn interface_addresses() -> io::Result<HashMap<String, Vec<String>>> {
+    let mut ifaddrs: *mut libc::ifaddrs = std::ptr::null_mut();
+    // SAFETY: on success the list is walked read-only and freed once below.
+    if unsafe { libc::getifaddrs(&mut ifaddrs) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut addresses: HashMap<String, Vec<String>> = HashMap::new();
+    let mut entry = ifaddrs;
+    while !entry.is_null() {
+        // SAFETY: `entry` is a node of the list returned by getifaddrs, and
+        // the address and netmask point to structs of the family they report.
+        let (name, address) = unsafe {
+            let ifa = &*entry;
+            entry = ifa.ifa_next;
+            if ifa.ifa_addr.is_null() {
+                continue;
+            }
+            let name = CStr::from_ptr(ifa.ifa_name).to_string_lossy().into_owned();
+            let address = match i32::from((*ifa.ifa_addr).sa_family) {
+                libc::AF_INET => {
+                    let addr = &*(ifa.ifa_addr as *const libc::sockaddr_in);
+                    let ip = Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr));
+                    let prefix = (!ifa.ifa_netmask.is_null()).then(|| {
+                        let mask = &*(ifa.ifa_netmask as *const libc::sockaddr_in);
+                        prefix_len(&mask.sin_addr.s_addr.to_ne_bytes())
+                    });
+                    (ip.to_string(), prefix)
+                }
+                libc::AF_INET6 => {
+                    let addr = &*(ifa.ifa_addr as *const libc::sockaddr_in6);
+                    let ip = Ipv6Addr::from(addr.sin6_addr.s6_addr);
+                    let prefix = (!ifa.ifa_netmask.is_null()).then(|| {
+                        let mask = &*(ifa.ifa_netmask as *const libc::sockaddr_in6);
+                        prefix_len(&mask.sin6_addr.s6_addr)
+                    });
+                    (ip.to_string(), prefix)
+                }
+                // Link layer entries, the MAC address comes from sysfs
+                _ => continue,
+            };
+            (name, address)
+        };
+        let formatted = match address {
+            (ip, Some(prefix)) => format!("{}/{}", ip, prefix),
+            (ip, None) => ip,
+        };
+        addresses.entry(name).or_default().push(formatted);
+    }
+
+    // SAFETY: `ifaddrs` came from getifaddrs and is not used afterwards.
+    unsafe { libc::freeifaddrs(ifaddrs) };
+    Ok(addresses)
+}
+
+pub async fn read_network_info(
+    procfs_root: &Path,
+    sysfs_root: &Path,
+) -> Result<NetworkInfo, ParseError> {
+    let net_dev_path = procfs_root.join("net/dev");
+    let mut interfaces = parse_net_dev(&read_file(&net_dev_path).await?, &net_dev_path);
+
+    // Addresses are optional; the counters are still worth reporting
+    let mut addresses = interface_addresses().unwrap_or_else(|e| {
+        warn!("Cannot list interface addresses: {}", e);
+        HashMap::new()
+    });
+    for interface in interfaces.iter_mut() {
+        fill_link_state(interface, sysfs_root).await;
+        interface.addresses = addresses.remove(&interface.name).unwrap_or_default();
+    }
+
+    Ok(NetworkInfo { interfaces })
+}
+
+pub async fn collect_network(collection: &mut Collection, procfs_root: &Path, sysfs_root: &Path) {
+    match read_network_info(procfs_root, sysfs_root).await {
+        Ok(network_info) => {
+            collection.status.network = network_info;
+            collection.status.mark_collected(Section::Network, None);
+        }
+        Err(e) => {
+            error!("Failed to read network statistics: {}", e);
+            collection.record_error(Section::Network, &e);
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use crate::models::{ProcessInfo, ProcessesInfo};
+use serde::Deserialize;
+use std::cmp::Ordering;
+use thiserror::Error;
+
+pub const SORT_FIELDS: &[&str] = &[
+    "pid",
+    "name",
+    "state",
+    "user",
+    "group",
+    "memory_rss",
+    "memory_virt",
+    "swap",
+    "threads",
+    "utime",
+    "start_time",
+    "cpu_percent",
+    "memory_rss_rate",
+    "first_seen",
+];
+
+#[derive(Error, Debug)]
+pub enum QueryError {
+    #[error("Unknown sort field '{0}', expected one of: {fields}", fields = SORT_FIELDS.join(", "))]
+    UnknownSortField(String),
+    #[error("Unknown sort order '{0}', expected 'asc' or 'desc'")]
+    UnknownOrder(String),
+}
+
+// Query string of GET /processes, e.g.
+// ?sort=memory_rss&order=desc&limit=20&offset=0&user=pi&state=R&name~=python
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ProcessQuery {
+    sort: Option<String>,
+    order: Option<String>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+    user: Option<String>,
+    state: Option<String>,
+    // Case-insensitive substring match on the process name
+    #[serde(rename = "name~")]
+    name_contains: Option<String>,
+}
+
+// Entries without a value stay at the end in both orders.
+fn cmp_option<T, F>(a: Option<T>, b: Option<T>, descending: bool, cmp: F) -> Ordering
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    match (a, b) {
+        (Some(a), Some(b)) if descending => cmp(&b, &a),
+        (Some(a), Some(b)) => cmp(&a, &b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+fn compare_by(field: &str, descending: bool, a: &ProcessInfo, b: &ProcessInfo) -> Ordering {
+    let d = descending;
+    match field {
+        "pid" => cmp_option(a.pid, b.pid, d, Ord::cmp),
+        "name" => cmp_option(a.name.as_ref(), b.name.as_ref(), d, Ord::cmp),
+        "state" => cmp_option(a.state_code.as_ref(), b.state_code.as_ref(), d, Ord::cmp),
+        "user" => cmp_option(a.user.as_ref(), b.user.as_ref(), d, Ord::cmp),
+        "group" => cmp_option(a.group.as_ref(), b.group.as_ref(), d, Ord::cmp),
+        "memory_rss" => cmp_option(a.memory_rss, b.memory_rss, d, Ord::cmp),
+        "memory_virt" => cmp_option(a.memory_virt, b.memory_virt, d, Ord::cmp),
+        "swap" => cmp_option(a.swap, b.swap, d, Ord::cmp),
+        "threads" => cmp_option(a.threads, b.threads, d, Ord::cmp),
+        "utime" => cmp_option(a.utime, b.utime, d, Ord::cmp),
+        "start_time" => cmp_option(a.start_time, b.start_time, d, Ord::cmp),
+        "cpu_percent" => cmp_option(a.cpu_percent, b.cpu_percent, d, f32::total_cmp),
+        "memory_rss_rate" => cmp_option(a.memory_rss_rate, b.memory_rss_rate, d, f64::total_cmp),
+        "first_seen" => cmp_option(a.first_seen, b.first_seen, d, Ord::cmp),
+        _ => Ordering::Equal,
+    }
+}
+
+impl ProcessQuery {
+    fn matches(&self, proc: &ProcessInfo, name_needle: Option<&str>) -> bool {
+        if let Some(user) = &self.user {
+            if proc.user.as_deref() != Some(user.as_str()) {
+                return false;
+            }
+        }
+        if let Some(state) = &self.state {
+            if proc.state_code.as_deref() != Some(state.as_str()) {
+                return false;
+            }
+        }
+        if let Some(needle) = name_needle {
+            match &proc.name {
+                Some(name) if name.to_lowercase().contains(needle) => {}
+                _ => return false,
+            }
+        }
+        true
+    }
+
+    // Filters, sorts and pages `processes`. `total` in the result is the
+    // number of matches before paging.
+    pub fn apply(&self, processes: &[ProcessInfo]) -> Result<ProcessesInfo, QueryError> {
+        let descending = match self.order.as_deref() {
+            None | Some("asc") => false,
+            Some("desc") => true,
+            Some(other) => return Err(QueryError::UnknownOrder(other.to_string())),
+        };
+        if let Some(field) = &self.sort {
+            if !SORT_FIELDS.contains(&field.as_str()) {
+                return Err(QueryError::UnknownSortField(field.clone()));
+            }
+        }
+
+        let name_needle = self.name_contains.as_ref().map(|n| n.to_lowercase());
+        let mut selected: Vec<ProcessInfo> = processes
+            .iter()
+            .filter(|p| self.matches(p, name_needle.as_deref()))
+            .cloned()
+            .collect();
+
+        if let Some(field) = &self.sort {
+            selected.sort_by(|a, b| compare_by(field, descending, a, b));
+        }
+
+        let total = selected.len();
+        let processes = selected
+            .into_iter()
+            .skip(self.offset.unwrap_or(0))
+            .take(self.limit.unwrap_or(usize::MAX))
+            .collect();
+
+        Ok(ProcessesInfo {
+            processes,
+            total: Some(total),
+        })
+    }
+}
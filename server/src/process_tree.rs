@@ -0,0 +1,77 @@
+use crate::models::{ProcessInfo, ProcessTreeNode};
+use std::collections::{HashMap, HashSet};
+
+// Maps each PID to the PIDs of its direct children, in list order.
+fn children_by_parent(processes: &[ProcessInfo]) -> HashMap<u32, Vec<u32>> {
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    for proc in processes {
+        if let (Some(pid), Some(ppid)) = (proc.pid, proc.ppid) {
+            if pid != ppid {
+                children.entry(ppid).or_default().push(pid);
+            }
+        }
+    }
+    children
+}
+
+fn build_node(
+    pid: u32,
+    by_pid: &HashMap<u32, &ProcessInfo>,
+    children: &HashMap<u32, Vec<u32>>,
+    visited: &mut HashSet<u32>,
+) -> Option<ProcessTreeNode> {
+    // A snapshot taken while processes are reparented can contain cycles
+    if !visited.insert(pid) {
+        return None;
+    }
+    let process = (*by_pid.get(&pid)?).clone();
+    let children = children
+        .get(&pid)
+        .into_iter()
+        .flatten()
+        .filter_map(|&child| build_node(child, by_pid, children, visited))
+        .collect();
+    Some(ProcessTreeNode { process, children })
+}
+
+// Nests the flat process list by parent PID. Processes whose parent is not
+// part of the list become roots.
+pub fn build_tree(processes: &[ProcessInfo]) -> Vec<ProcessTreeNode> {
+    let by_pid: HashMap<u32, &ProcessInfo> = processes
+        .iter()
+        .filter_map(|p| p.pid.map(|pid| (pid, p)))
+        .collect();
+    let children = children_by_parent(processes);
+    let mut visited = HashSet::new();
+
+    processes
+        .iter()
+        .filter_map(|p| p.pid.map(|pid| (pid, p.ppid)))
+        .filter(|(pid, ppid)| match ppid {
+            Some(ppid) => *ppid == *pid || !by_pid.contains_key(ppid),
+            None => true,
+        })
+        .filter_map(|(pid, _)| build_node(pid, &by_pid, &children, &mut visited))
+        .collect()
+}
+
+// Returns `pid` and all of its descendants ordered so that every process
+// comes after its children, i.e. the order in which to terminate a subtree.
+pub fn subtree_leaf_first(processes: &[ProcessInfo], pid: u32) -> Vec<u32> {
+    let children = children_by_parent(processes);
+    let mut order = Vec::new();
+    let mut visited = HashSet::new();
+    // Iterative post-order walk: (pid, children already pushed)
+    let mut stack = vec![(pid, false)];
+    while let Some((current, expanded)) = stack.pop() {
+        if expanded {
+            order.push(current);
+        } else if visited.insert(current) {
+            stack.push((current, true));
+            for &child in children.get(&current).into_iter().flatten() {
+                stack.push((child, false));
+            }
+        }
+    }
+    order
+}
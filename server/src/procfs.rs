@@ -0,0 +1,534 @@
+use crate::clock::unix_now_ms;
+use crate::data_source::ParseError;
+use crate::disks::collect_disks;
+use crate::kernel_fs::read_file;
+use crate::models::*;
+use crate::network::collect_network;
+use crate::source::{
+    Collection, CounterKind, DetailsLookup, ProcessIdentity, SourceCapabilities, StatusSource,
+};
+use crate::thermal::collect_thermal;
+use futures_util::future::BoxFuture;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+use tokio::fs;
+use tracing::{debug, error, warn};
+
+// Native collector reading the kernel interfaces directly, so the API can run
+// without the rpi_watch daemon. Fills the same models as the status file parser.
+
+fn value_error(file_path: &Path, line: &str, value: &str, message: &str) -> ParseError {
+    ParseError::ValueFormat {
+        file: file_path.to_string_lossy().into_owned(),
+        message: message.to_string(),
+        line: line.to_string(),
+        value: value.to_string(),
+    }
+}
+
+// Parses one "cpu" / "cpuN" line of /proc/stat into cumulative jiffy counters.
+fn parse_stat_cpu_line(line: &str, file_path: &Path) -> Result<CpuStat, ParseError> {
+    let mut values = Vec::with_capacity(7);
+    for value_str in line.split_whitespace().skip(1).take(7) {
+        let value = value_str
+            .parse::<u64>()
+            .map_err(|_| value_error(file_path, line, value_str, "Could not parse as u64"))?;
+        values.push(value);
+    }
+    if values.len() < 7 {
+        return Err(ParseError::FileFormat {
+            file: file_path.to_string_lossy().into_owned(),
+            message: "Expected at least 7 counters".to_string(),
+            line: line.to_string(),
+        });
+    }
+
+    Ok(CpuStat {
+        user_norm: Some(values[0]),
+        user_nice: Some(values[1]),
+        kernel: Some(values[2]),
+        idle: Some(values[3]),
+        iowait: Some(values[4]),
+        irq: Some(values[5]),
+        soft_irq: Some(values[6]),
+        ..Default::default()
+    })
+}
+
+async fn read_cpu_usage(procfs_root: &Path) -> Result<CpuUsage, ParseError> {
+    let file_path = procfs_root.join("stat");
+    let content = read_file(&file_path).await?;
+
+    let mut cpu_usage = CpuUsage::default();
+    let mut cores = Vec::new();
+
+    for line in content.lines() {
+        let Some(label) = line.split_whitespace().next() else {
+            continue;
+        };
+        if label == "cpu" {
+            cpu_usage.full = Some(parse_stat_cpu_line(line, &file_path)?);
+        } else if let Some(id_str) = label.strip_prefix("cpu") {
+            match id_str.parse::<u32>() {
+                Ok(core_id) => match parse_stat_cpu_line(line, &file_path) {
+                    Ok(stats) => cores.push(CoreStat { core_id, stats }),
+                    Err(e) => warn!("{}", e),
+                },
+                Err(_) => warn!(
+                    "{}: Failed to parse core ID from '{}'",
+                    file_path.display(),
+                    line
+                ),
+            }
+        }
+    }
+
+    if !cores.is_empty() {
+        cpu_usage.cores = Some(cores);
+    }
+    Ok(cpu_usage)
+}
+
+// Picks the thermal zone describing the SoC: the first zone whose type mentions
+// "cpu" or "soc", otherwise thermal_zone0.
+async fn read_cpu_temperature(sysfs_root: &Path) -> Option<f32> {
+    let thermal_dir = sysfs_root.join("class/thermal");
+    let mut zones = Vec::new();
+    let mut entries = fs::read_dir(&thermal_dir).await.ok()?;
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with("thermal_zone") {
+            zones.push(entry.path());
+        }
+    }
+    zones.sort();
+
+    let mut selected = zones.first().cloned()?;
+    for zone in &zones {
+        if let Ok(zone_type) = fs::read_to_string(zone.join("type")).await {
+            let zone_type = zone_type.trim().to_ascii_lowercase();
+            if zone_type.contains("cpu") || zone_type.contains("soc") {
+                selected = zone.clone();
+                break;
+            }
+        }
+    }
+
+    let temp_path = selected.join("temp");
+    let content = fs::read_to_string(&temp_path).await.ok()?;
+    match content.trim().parse::<f32>() {
+        Ok(temp_milli) => Some(temp_milli / 1000.0),
+        Err(_) => {
+            warn!(
+                "{}: Could not parse temperature '{}'",
+                temp_path.display(),
+                content.trim()
+            );
+            None
+        }
+    }
+}
+
+async fn read_cpu_info(procfs_root: &Path, sysfs_root: &Path) -> Result<CpuInfo, ParseError> {
+    let cpu_usage = read_cpu_usage(procfs_root).await?;
+    Ok(CpuInfo {
+        cpu_temperature: read_cpu_temperature(sysfs_root).await,
+        cpu_usage: Some(cpu_usage),
+    })
+}
+
+// Returns the first number of a "Key:   1234 kB" style line.
+fn meminfo_value<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    line.strip_prefix(key)
+        .and_then(|s| s.strip_prefix(':'))
+        .and_then(|s| s.split_whitespace().next())
+}
+
+async fn read_memory_info(procfs_root: &Path) -> Result<MemoryInfo, ParseError> {
+    let file_path = procfs_root.join("meminfo");
+    let content = read_file(&file_path).await?;
+    let mut memory_info = MemoryInfo::default();
+
+    for line in content.lines() {
+        let (target, value_str) = if let Some(v) = meminfo_value(line, "MemTotal") {
+            (&mut memory_info.total, v)
+        } else if let Some(v) = meminfo_value(line, "MemFree") {
+            (&mut memory_info.free, v)
+        } else if let Some(v) = meminfo_value(line, "MemAvailable") {
+            (&mut memory_info.available, v)
+        } else if let Some(v) = meminfo_value(line, "Buffers") {
+            (&mut memory_info.buffers, v)
+        } else if let Some(v) = meminfo_value(line, "Cached") {
+            (&mut memory_info.cached, v)
+        } else if let Some(v) = meminfo_value(line, "Shmem") {
+            (&mut memory_info.shared, v)
+        } else if let Some(v) = meminfo_value(line, "Dirty") {
+            (&mut memory_info.dirty, v)
+        } else if let Some(v) = meminfo_value(line, "Slab") {
+            (&mut memory_info.slab, v)
+        } else if let Some(v) = meminfo_value(line, "SwapTotal") {
+            (&mut memory_info.swap_total, v)
+        } else if let Some(v) = meminfo_value(line, "SwapFree") {
+            (&mut memory_info.swap_free, v)
+        } else {
+            continue;
+        };
+        match value_str.parse::<u64>() {
+            Ok(val) => *target = Some(val),
+            Err(_) => warn!(
+                "{}",
+                value_error(&file_path, line, value_str, "Could not parse as u64")
+            ),
+        }
+    }
+    Ok(memory_info)
+}
+
+// Reads /proc/loadavg ("0.15 0.10 0.05 1/123 4567") and /proc/uptime.
+async fn read_system_info(procfs_root: &Path) -> Result<SystemInfo, ParseError> {
+    let mut system_info = SystemInfo::default();
+
+    let file_path = procfs_root.join("loadavg");
+    let content = read_file(&file_path).await?;
+    let line = content.lines().next().unwrap_or_default();
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    if fields.len() < 4 {
+        return Err(ParseError::FileFormat {
+            file: file_path.to_string_lossy().into_owned(),
+            message: "Expected load averages and task counts".to_string(),
+            line: line.to_string(),
+        });
+    }
+    let parse_load = |value_str: &str| {
+        value_str
+            .parse::<f32>()
+            .map_err(|_| value_error(&file_path, line, value_str, "Could not parse as f32"))
+    };
+    system_info.load1 = Some(parse_load(fields[0])?);
+    system_info.load5 = Some(parse_load(fields[1])?);
+    system_info.load15 = Some(parse_load(fields[2])?);
+    let (running, total) = fields[3].split_once('/').unwrap_or_default();
+    let parse_tasks = |value_str: &str| {
+        value_str
+            .parse::<u32>()
+            .map_err(|_| value_error(&file_path, line, value_str, "Could not parse as u32"))
+    };
+    system_info.running_tasks = Some(parse_tasks(running)?);
+    system_info.total_tasks = Some(parse_tasks(total)?);
+
+    let file_path = procfs_root.join("uptime");
+    let content = read_file(&file_path).await?;
+    let line = content.lines().next().unwrap_or_default();
+    let value_str = line.split_whitespace().next().unwrap_or_default();
+    let uptime = value_str
+        .parse::<f64>()
+        .map_err(|_| value_error(&file_path, line, value_str, "Could not parse as f64"))?;
+    system_info.uptime_secs = Some(uptime);
+    system_info.boot_time = Some(unix_now_ms().saturating_sub((uptime * 1000.0) as u64));
+
+    Ok(system_info)
+}
+
+// Maps numeric ids to names using a passwd/group style file. Missing files are
+// not an error; ids are then reported numerically.
+async fn read_id_names(file_path: &Path) -> HashMap<u32, String> {
+    let mut names = HashMap::new();
+    let Ok(content) = fs::read_to_string(file_path).await else {
+        debug!("{}: not readable, using numeric ids", file_path.display());
+        return names;
+    };
+    for line in content.lines() {
+        let fields: Vec<&str> = line.split(':').collect();
+        if fields.len() >= 3 {
+            if let Ok(id) = fields[2].parse::<u32>() {
+                names.entry(id).or_insert_with(|| fields[0].to_string());
+            }
+        }
+    }
+    names
+}
+
+fn resolve_id(value: &str, names: &HashMap<u32, String>) -> Option<String> {
+    // "Uid:\t1000\t1000\t1000\t1000" - the first field is the real id
+    let id_str = value.split_whitespace().next()?;
+    match id_str.parse::<u32>().ok().and_then(|id| names.get(&id)) {
+        Some(name) => Some(name.clone()),
+        None => Some(id_str.to_string()),
+    }
+}
+
+// Parses /proc/[pid]/stat. The command name may contain spaces and parentheses,
+// so fields are counted from the last ')'.
+fn parse_pid_stat(content: &str) -> Option<Vec<&str>> {
+    let (_, rest) = content.rsplit_once(')')?;
+    Some(rest.split_whitespace().collect())
+}
+
+async fn read_process(
+    pid_dir: &Path,
+    pid: u32,
+    users: &HashMap<u32, String>,
+    groups: &HashMap<u32, String>,
+) -> Result<ProcessInfo, ParseError> {
+    let status_path = pid_dir.join("status");
+    let content = read_file(&status_path).await?;
+
+    let mut proc = ProcessInfo {
+        pid: Some(pid),
+        ..Default::default()
+    };
+
+    for line in content.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match key {
+            "Name" => proc.name = Some(value.to_string()),
+            "State" => {
+                if let Some((code, desc_part)) = value.split_once('(') {
+                    proc.state_code = Some(code.trim().to_string());
+                    proc.state_description =
+                        Some(desc_part.trim_end_matches(')').trim().to_string());
+                } else {
+                    proc.state_code = Some(value.to_string());
+                }
+            }
+            "PPid" => proc.ppid = value.parse::<u32>().ok(),
+            "Uid" => proc.user = resolve_id(value, users),
+            "Gid" => proc.group = resolve_id(value, groups),
+            "VmRSS" | "VmSize" | "VmSwap" | "Threads" => {
+                let Some(value_str) = value.split_whitespace().next() else {
+                    continue;
+                };
+                match value_str.parse::<u64>() {
+                    Ok(val) => match key {
+                        "VmRSS" => proc.memory_rss = Some(val),
+                        "VmSize" => proc.memory_virt = Some(val),
+                        "VmSwap" => proc.swap = Some(val),
+                        _ => proc.threads = u32::try_from(val).ok(),
+                    },
+                    Err(_) => warn!(
+                        "{}",
+                        value_error(&status_path, line, value_str, "Could not parse as u64")
+                    ),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // utime + stime in clock ticks since the process started. rpi_watch
+    // reports "Utime" over its last interval instead; `CounterKind` tells
+    // the trackers which one they get.
+    let stat_path = pid_dir.join("stat");
+    match read_file(&stat_path).await {
+        Ok(stat) => {
+            if let Some(fields) = parse_pid_stat(&stat) {
+                // Field 14 (utime), 15 (stime) and 22 (starttime); index 0
+                // here is field 3 (state)
+                let utime = fields.get(11).and_then(|s| s.parse::<u64>().ok());
+                let stime = fields.get(12).and_then(|s| s.parse::<u64>().ok());
+                if let (Some(utime), Some(stime)) = (utime, stime) {
+                    proc.utime = Some(utime + stime);
+                }
+                proc.start_time = fields.get(19).and_then(|s| s.parse::<u64>().ok());
+            } else {
+                warn!(
+                    "{}: Malformed stat line: '{}'",
+                    stat_path.display(),
+                    stat.trim()
+                );
+            }
+        }
+        Err(e) => debug!("{}", e),
+    }
+
+    Ok(proc)
+}
+
+async fn read_processes_info(procfs_root: &Path) -> Result<ProcessesInfo, ParseError> {
+    let mut entries = fs::read_dir(procfs_root)
+        .await
+        .map_err(|e| ParseError::Io(procfs_root.to_string_lossy().into_owned(), e))?;
+
+    let users = read_id_names(Path::new("/etc/passwd")).await;
+    let groups = read_id_names(Path::new("/etc/group")).await;
+
+    let mut processes_info = ProcessesInfo::default();
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let Ok(pid) = entry.file_name().to_string_lossy().parse::<u32>() else {
+            continue;
+        };
+        match read_process(&entry.path(), pid, &users, &groups).await {
+            Ok(proc) => processes_info.processes.push(proc),
+            // Processes routinely exit between readdir and open
+            Err(e) => debug!("Skipping PID {}: {}", pid, e),
+        }
+    }
+    processes_info.processes.sort_by_key(|p| p.pid);
+
+    Ok(processes_info)
+}
+
+pub async fn read_process_identity(procfs_root: &Path, pid: u32) -> Option<ProcessIdentity> {
+    let stat = read_file(&procfs_root.join(pid.to_string()).join("stat"))
+        .await
+        .ok()?;
+    let fields = parse_pid_stat(&stat)?;
+    // Field 4 (ppid) and 22 (starttime), see `read_process`
+    Some(ProcessIdentity {
+        ppid: fields.get(1)?.parse().ok()?,
+        start_time: fields.get(19)?.parse().ok()?,
+    })
+}
+
+async fn read_process_details(
+    procfs_root: &Path,
+    pid: u32,
+    expected_start_time: Option<u64>,
+) -> DetailsLookup {
+    let Some(identity) = read_process_identity(procfs_root, pid).await else {
+        return DetailsLookup::Unavailable;
+    };
+    if expected_start_time.is_some_and(|expected| expected != identity.start_time) {
+        return DetailsLookup::PidReused {
+            start_time: identity.start_time,
+        };
+    }
+    let pid_dir = procfs_root.join(pid.to_string());
+    let mut details = ProcessExtendedInfo::default();
+
+    // Arguments are NUL separated; kernel threads have an empty command line
+    if let Ok(cmdline) = fs::read(pid_dir.join("cmdline")).await {
+        let args: Vec<String> = cmdline
+            .split(|&b| b == 0)
+            .filter(|arg| !arg.is_empty())
+            .map(|arg| String::from_utf8_lossy(arg).into_owned())
+            .collect();
+        if !args.is_empty() {
+            details.cmdline = Some(args);
+        }
+    }
+
+    // Listing fd/ needs the same user or CAP_SYS_PTRACE
+    match fs::read_dir(pid_dir.join("fd")).await {
+        Ok(mut entries) => {
+            let mut count = 0;
+            while let Ok(Some(_)) = entries.next_entry().await {
+                count += 1;
+            }
+            details.open_files = Some(count);
+        }
+        Err(e) => debug!("PID {}: cannot list open files: {}", pid, e),
+    }
+
+    DetailsLookup::Found(details)
+}
+
+pub async fn read_procfs(procfs_root: &Path, sysfs_root: &Path) -> Collection {
+    debug!(
+        "Reading native sources: procfs='{}', sysfs='{}'",
+        procfs_root.display(),
+        sysfs_root.display()
+    );
+
+    let mut collection = Collection::default();
+
+    match read_cpu_info(procfs_root, sysfs_root).await {
+        Ok(cpu_info) => {
+            collection.status.cpu = cpu_info;
+            collection.status.mark_collected(Section::Cpu, None);
+        }
+        Err(e) => {
+            error!("Failed to read CPU statistics: {}", e);
+            collection.record_error(Section::Cpu, &e);
+        }
+    }
+
+    match read_memory_info(procfs_root).await {
+        Ok(memory_info) => {
+            collection.status.memory = memory_info;
+            collection.status.mark_collected(Section::Memory, None);
+        }
+        Err(e) => {
+            error!("Failed to read memory statistics: {}", e);
+            collection.record_error(Section::Memory, &e);
+        }
+    }
+
+    match read_processes_info(procfs_root).await {
+        Ok(processes_info) => {
+            collection.status.processes = processes_info;
+            collection.status.mark_collected(Section::Processes, None);
+        }
+        Err(e) => {
+            error!("Failed to read process list: {}", e);
+            collection.record_error(Section::Processes, &e);
+        }
+    }
+
+    match read_system_info(procfs_root).await {
+        Ok(system_info) => {
+            collection.status.system = system_info;
+            collection.status.mark_collected(Section::System, None);
+        }
+        Err(e) => {
+            error!("Failed to read load average and uptime: {}", e);
+            collection.record_error(Section::System, &e);
+        }
+    }
+
+    collect_disks(&mut collection, procfs_root).await;
+    collect_network(&mut collection, procfs_root, sysfs_root).await;
+    collect_thermal(&mut collection, sysfs_root).await;
+
+    // There is no kernel interface for the SPI thermocouple; the external
+    // temperature section stays empty with this source.
+
+    debug!(
+        "Native collection complete. Status: {:?}",
+        collection.status
+    );
+    collection
+}
+
+#[derive(Debug, Clone)]
+pub struct ProcfsSource {
+    pub procfs_root: PathBuf,
+    pub sysfs_root: PathBuf,
+}
+
+impl StatusSource for ProcfsSource {
+    fn name(&self) -> &'static str {
+        "procfs"
+    }
+
+    fn capabilities(&self) -> SourceCapabilities {
+        SourceCapabilities {
+            cpu: true,
+            memory: true,
+            processes: true,
+            external_temperature: false,
+            system: true,
+            disks: true,
+            network: true,
+            thermal: true,
+            cpu_counters: CounterKind::Cumulative,
+        }
+    }
+
+    fn collect(&self) -> BoxFuture<'_, Collection> {
+        Box::pin(read_procfs(&self.procfs_root, &self.sysfs_root))
+    }
+
+    fn process_details(&self, pid: u32, start_time: Option<u64>) -> BoxFuture<'_, DetailsLookup> {
+        Box::pin(read_process_details(&self.procfs_root, pid, start_time))
+    }
+
+    fn process_identity(&self, pid: u32) -> BoxFuture<'_, Option<ProcessIdentity>> {
+        Box::pin(read_process_identity(&self.procfs_root, pid))
+    }
+}
@@ -0,0 +1,177 @@
+use crate::clock::unix_now_ms;
+use crate::config::{DataSourceKind, Settings};
+use crate::data_source::{ParseError, ReadRetry, StatusFileSource};
+use crate::models::{FileFindings, LineFinding, ProcessExtendedInfo, Section, SystemStatus};
+use crate::procfs::ProcfsSource;
+use futures_util::future::BoxFuture;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+// How the CPU jiffy counters of a source behave between two collections.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CounterKind {
+    // Monotonic totals since boot (/proc/stat); usage needs two samples
+    #[default]
+    Cumulative,
+    // Already the difference over the producer's last interval
+    Interval,
+}
+
+// Result of `StatusSource::process_details`
+#[derive(Debug)]
+pub enum DetailsLookup {
+    Found(ProcessExtendedInfo),
+    // The source has no details, or the process has exited
+    Unavailable,
+    // The PID now belongs to a process started at `start_time`
+    PidReused { start_time: u64 },
+}
+
+// Parent and start time of a running process, to tell whether a PID still
+// belongs to the process of the snapshot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProcessIdentity {
+    pub ppid: u32,
+    pub start_time: u64,
+}
+
+// Sections that depend on files or hardware not every board has. /health
+// counts them only once they have been collected.
+pub const OPTIONAL_SECTIONS: [Section; 2] = [Section::ExternalTemperature, Section::System];
+
+// Which parts of `SystemStatus` a source is able to fill. Sections a source
+// does not provide are left at their defaults.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SourceCapabilities {
+    pub cpu: bool,
+    pub memory: bool,
+    pub processes: bool,
+    pub external_temperature: bool,
+    pub system: bool,
+    pub disks: bool,
+    pub network: bool,
+    pub thermal: bool,
+    pub cpu_counters: CounterKind,
+}
+
+impl SourceCapabilities {
+    pub fn provides(&self, section: Section) -> bool {
+        match section {
+            Section::Cpu => self.cpu,
+            Section::Memory => self.memory,
+            Section::Processes => self.processes,
+            Section::ExternalTemperature => self.external_temperature,
+            Section::System => self.system,
+            Section::Disks => self.disks,
+            Section::Network => self.network,
+            Section::Thermal => self.thermal,
+        }
+    }
+}
+
+// A section the source failed to read on this collection
+#[derive(Debug, Clone)]
+pub struct SectionError {
+    pub section: Section,
+    pub file: String,
+    pub message: String,
+}
+
+// Result of one collection: the snapshot plus the sections that failed, which
+// the updater uses to decide whether to keep the previous data.
+#[derive(Debug, Default)]
+pub struct Collection {
+    pub status: SystemStatus,
+    pub errors: Vec<SectionError>,
+    // Line-level findings of the files read, collected in strict parse mode
+    pub findings: Vec<FileFindings>,
+}
+
+impl Collection {
+    pub fn record_error(&mut self, section: Section, error: &ParseError) {
+        self.errors.push(SectionError {
+            section,
+            file: error.file().to_string(),
+            message: error.to_string(),
+        });
+    }
+
+    pub fn record_findings(&mut self, section: Section, file: &Path, findings: Vec<LineFinding>) {
+        self.findings.push(FileFindings {
+            file: file.to_string_lossy().into_owned(),
+            section,
+            collected_at: unix_now_ms(),
+            findings,
+        });
+    }
+
+    // Whether `section` could not be read at all. A section made of several
+    // files that is marked collected despite errors was read in part.
+    pub fn failed(&self, section: Section) -> bool {
+        self.errors.iter().any(|e| e.section == section)
+            && !self.status.freshness.contains_key(&section)
+    }
+}
+
+// A provider of system status snapshots polled by the background updater.
+pub trait StatusSource: Send + Sync + std::fmt::Debug {
+    // Short name used in logs
+    fn name(&self) -> &'static str;
+
+    fn capabilities(&self) -> SourceCapabilities;
+
+    // Produces a complete snapshot. Sections that fail are left at their
+    // defaults and reported in `Collection::errors`.
+    fn collect(&self) -> BoxFuture<'_, Collection>;
+
+    // Like `collect`, but only `sections` need to be filled; the updater keeps
+    // the others from the previous snapshot. Sources that cannot read parts
+    // separately collect everything.
+    fn collect_sections(&self, _sections: &[Section]) -> BoxFuture<'_, Collection> {
+        self.collect()
+    }
+
+    // Files whose modification means a section should be re-read, for the
+    // watch refresh mode. Sources without such files are polled.
+    fn watched_files(&self) -> Vec<(Section, PathBuf)> {
+        Vec::new()
+    }
+
+    // Looks up detail that is too expensive to collect for every process on
+    // every refresh. `start_time` is the one in the snapshot, to tell whether
+    // the PID still belongs to the same process.
+    fn process_details(&self, _pid: u32, _start_time: Option<u64>) -> BoxFuture<'_, DetailsLookup> {
+        Box::pin(async { DetailsLookup::Unavailable })
+    }
+
+    // The identity of the process now running as `pid`, None when there is
+    // none. Read from procfs whatever the source, since the controller acts
+    // on the live system.
+    fn process_identity(&self, pid: u32) -> BoxFuture<'_, Option<ProcessIdentity>>;
+}
+
+pub fn build_source(settings: &Settings) -> Arc<dyn StatusSource> {
+    match settings.data_source {
+        DataSourceKind::StatusFiles => Arc::new(StatusFileSource {
+            cpu_file: settings.cpu_file.clone(),
+            ram_file: settings.ram_file.clone(),
+            proc_file: settings.proc_file.clone(),
+            ext_sensors: settings.ext_sensors.clone(),
+            system_file: settings.system_file.clone(),
+            procfs_root: settings.procfs_root.clone(),
+            sysfs_root: settings.sysfs_root.clone(),
+            read_retry: ReadRetry {
+                attempts: settings.read_attempts.max(1),
+                backoff: Duration::from_millis(settings.read_retry_backoff_ms),
+            },
+            strict_parsing: settings.strict_parsing,
+        }),
+        DataSourceKind::Procfs => Arc::new(ProcfsSource {
+            procfs_root: settings.procfs_root.clone(),
+            sysfs_root: settings.sysfs_root.clone(),
+        }),
+    }
+}
@@ -0,0 +1,118 @@
+use crate::data_source::ParseError;
+use crate::models::{CoreStat, CpuInfo, ExternalSensorInfo, MemoryInfo, ProcessesInfo, SystemInfo};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::Value;
+use std::path::Path;
+
+// Highest status file schema version this server understands. Fields added
+// within a version are ignored by older servers.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Deserialize)]
+struct Document<T> {
+    version: u32,
+    #[serde(flatten)]
+    data: T,
+}
+
+// A section that can be read from a JSON status file. Sections holding a list
+// also accept NDJSON: a header object followed by one list entry per line.
+pub trait JsonSection: DeserializeOwned {
+    fn push_entry(&mut self, _entry: Value) -> Result<(), String> {
+        Err("this file does not take one entry per line".to_string())
+    }
+}
+
+impl JsonSection for MemoryInfo {}
+
+impl JsonSection for ExternalSensorInfo {}
+
+impl JsonSection for SystemInfo {}
+
+impl JsonSection for ProcessesInfo {
+    fn push_entry(&mut self, entry: Value) -> Result<(), String> {
+        let process = serde_json::from_value(entry).map_err(|e| e.to_string())?;
+        self.processes.push(process);
+        Ok(())
+    }
+}
+
+impl JsonSection for CpuInfo {
+    fn push_entry(&mut self, entry: Value) -> Result<(), String> {
+        let core: CoreStat = serde_json::from_value(entry).map_err(|e| e.to_string())?;
+        self.cpu_usage
+            .get_or_insert_with(Default::default)
+            .cores
+            .get_or_insert_with(Vec::new)
+            .push(core);
+        Ok(())
+    }
+}
+
+// Status files in JSON start with an object; the text format never does.
+pub fn is_json(content: &str) -> bool {
+    content.trim_start().starts_with('{')
+}
+
+fn format_error(file_path: &Path, message: String, line: &str) -> ParseError {
+    ParseError::FileFormat {
+        file: file_path.to_string_lossy().into_owned(),
+        message,
+        line: line.to_string(),
+    }
+}
+
+// Parses a JSON document, or NDJSON when the content has more than one
+// non-empty line.
+pub fn parse<T: JsonSection>(content: &str, file_path: &Path) -> Result<T, ParseError> {
+    let mut lines = content.lines().map(str::trim).filter(|l| !l.is_empty());
+    let header = lines.next().unwrap_or_default();
+    let first_entry = lines.next();
+
+    let (document_text, entries): (&str, Vec<&str>) = match first_entry {
+        // A single line or a pretty-printed document
+        None => (header, Vec::new()),
+        Some(_) if serde_json::from_str::<Value>(header).is_err() => (content, Vec::new()),
+        Some(entry) => (header, std::iter::once(entry).chain(lines).collect()),
+    };
+
+    let document: Document<T> = serde_json::from_str(document_text).map_err(|e| {
+        if e.is_eof() {
+            // Cut off mid-write, worth another read
+            return ParseError::Torn {
+                file: file_path.to_string_lossy().into_owned(),
+                message: e.to_string(),
+            };
+        }
+        let line = document_text.lines().nth(e.line().saturating_sub(1));
+        format_error(file_path, e.to_string(), line.unwrap_or_default())
+    })?;
+    if document.version == 0 || document.version > SCHEMA_VERSION {
+        return Err(format_error(
+            file_path,
+            format!(
+                "Unsupported schema version {} (supported: 1 to {})",
+                document.version, SCHEMA_VERSION
+            ),
+            header,
+        ));
+    }
+
+    let mut data = document.data;
+    let last = entries.len().saturating_sub(1);
+    for (i, entry) in entries.iter().enumerate() {
+        let value = serde_json::from_str::<Value>(entry).map_err(|e| {
+            if e.is_eof() && i == last {
+                // The writer had not finished the last line yet
+                return ParseError::Torn {
+                    file: file_path.to_string_lossy().into_owned(),
+                    message: e.to_string(),
+                };
+            }
+            format_error(file_path, e.to_string(), entry)
+        })?;
+        data.push_entry(value)
+            .map_err(|message| format_error(file_path, message, entry))?;
+    }
+    Ok(data)
+}
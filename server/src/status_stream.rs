@@ -0,0 +1,148 @@
+use crate::models::{Section, StatusResponse, SystemStatus};
+use axum::extract::ws::{Message, WebSocket};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, str::FromStr, sync::Arc, time::Duration};
+use tokio::{sync::watch, time::Instant};
+use tracing::{debug, info};
+
+// GET /status/ws: pushes the subscribed sections to the client after every
+// refresh that re-collected one of them. The updater publishes each new
+// snapshot on a watch channel, which only keeps the latest one, so a client
+// that is slower than the refreshes skips snapshots instead of queueing them.
+
+// Messages from the client, e.g. {"action": "subscribe", "sections": ["cpu"]}
+#[derive(Deserialize, Debug)]
+#[serde(tag = "action", rename_all = "snake_case", deny_unknown_fields)]
+enum ClientMessage {
+    Subscribe { sections: Vec<String> },
+    Unsubscribe { sections: Vec<String> },
+}
+
+// Sent instead of a snapshot when a client message cannot be used
+#[derive(Serialize, Debug)]
+struct ErrorMessage {
+    error: String,
+}
+
+struct Subscriber {
+    sections: Vec<Section>,
+    // When each subscribed section had been collected in the last snapshot
+    // sent; a section missing here has not been sent since it was subscribed
+    sent: BTreeMap<Section, Option<u64>>,
+    min_interval: Duration,
+    next_send: Instant,
+}
+
+fn collected_at(status: &SystemStatus, section: Section) -> Option<u64> {
+    status
+        .freshness
+        .get(&section)
+        .and_then(|entry| entry.collected_at)
+}
+
+impl Subscriber {
+    // Whether a subscribed section was collected again since it was last sent
+    fn has_news(&self, status: &SystemStatus) -> bool {
+        self.sections
+            .iter()
+            .any(|&section| self.sent.get(&section) != Some(&collected_at(status, section)))
+    }
+
+    fn snapshot(&mut self, status: &SystemStatus) -> Message {
+        for &section in &self.sections {
+            self.sent.insert(section, collected_at(status, section));
+        }
+        self.next_send = Instant::now() + self.min_interval;
+        let response = StatusResponse::from_status(status, &self.sections);
+        Message::Text(serde_json::to_string(&response).unwrap_or_default())
+    }
+
+    fn handle(&mut self, text: &str) -> Result<(), String> {
+        let message: ClientMessage = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        // Nothing changes when one of the names is unknown
+        let parse = |names: Vec<String>| -> Result<Vec<Section>, String> {
+            names.iter().map(|name| Section::from_str(name)).collect()
+        };
+        match message {
+            ClientMessage::Subscribe { sections } => {
+                for section in parse(sections)? {
+                    if !self.sections.contains(&section) {
+                        self.sections.push(section);
+                    }
+                }
+                // Same order as GET /status, whatever the order of subscribing
+                self.sections.sort();
+            }
+            ClientMessage::Unsubscribe { sections } => {
+                for section in parse(sections)? {
+                    self.sections.retain(|s| *s != section);
+                    self.sent.remove(&section);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn error_message(error: String) -> Message {
+    Message::Text(serde_json::to_string(&ErrorMessage { error }).unwrap_or_default())
+}
+
+// Serves one client until it disconnects. `current` is the snapshot at the
+// time `updates` was subscribed, and `sections` the initial subscription;
+// snapshots are sent at most once per `min_interval`.
+pub async fn serve_client(
+    mut socket: WebSocket,
+    current: Arc<SystemStatus>,
+    mut updates: watch::Receiver<Arc<SystemStatus>>,
+    sections: Vec<Section>,
+    min_interval: Duration,
+) {
+    info!(
+        "/status/ws: client connected, sections {:?}, at most every {} ms",
+        sections,
+        min_interval.as_millis()
+    );
+    let mut subscriber = Subscriber {
+        sections,
+        sent: BTreeMap::new(),
+        min_interval,
+        next_send: Instant::now(),
+    };
+    let mut latest = current;
+
+    loop {
+        let pending = subscriber.has_news(&latest);
+        tokio::select! {
+            changed = updates.changed() => {
+                if changed.is_err() {
+                    // The updater has stopped
+                    break;
+                }
+                latest = updates.borrow_and_update().clone();
+            }
+            _ = tokio::time::sleep_until(subscriber.next_send), if pending => {
+                let message = subscriber.snapshot(&latest);
+                if socket.send(message).await.is_err() {
+                    break;
+                }
+            }
+            message = socket.recv() => {
+                match message {
+                    Some(Ok(Message::Text(text))) => {
+                        if let Err(e) = subscriber.handle(&text) {
+                            debug!("/status/ws: rejected message '{}': {}", text, e);
+                            if socket.send(error_message(e)).await.is_err() {
+                                break;
+                            }
+                        }
+                    }
+                    // Pings are answered by axum
+                    Some(Ok(Message::Binary(_) | Message::Ping(_) | Message::Pong(_))) => {}
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                }
+            }
+        }
+    }
+    info!("/status/ws: client disconnected");
+}
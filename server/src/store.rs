@@ -0,0 +1,598 @@
+use crate::clock::unix_now_ms;
+use crate::history::{from_f32, is_fresh, series_values, Buckets, HistoryRange, HISTORY_SECTIONS};
+use crate::models::{Section, SystemStatus};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use thiserror::Error;
+use tracing::{debug, error, info};
+
+// Keeps the history on disk, in an SQLite database under DATA_DIR, so that it
+// survives restarts. Raw samples are rolled up into minute and hour buckets,
+// and every resolution is deleted after its own retention period.
+
+const MINUTE_MS: u64 = 60_000;
+const HOUR_MS: u64 = 3_600_000;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS series (
+        id INTEGER PRIMARY KEY,
+        section TEXT NOT NULL,
+        name TEXT NOT NULL,
+        UNIQUE (section, name)
+    );
+    CREATE TABLE IF NOT EXISTS samples_raw (
+        series_id INTEGER NOT NULL,
+        at INTEGER NOT NULL,
+        value REAL NOT NULL,
+        PRIMARY KEY (series_id, at)
+    ) WITHOUT ROWID;
+    CREATE INDEX IF NOT EXISTS samples_raw_at ON samples_raw (at);
+    CREATE TABLE IF NOT EXISTS samples_1m (
+        series_id INTEGER NOT NULL,
+        start INTEGER NOT NULL,
+        min REAL NOT NULL,
+        avg REAL NOT NULL,
+        max REAL NOT NULL,
+        count INTEGER NOT NULL,
+        PRIMARY KEY (series_id, start)
+    ) WITHOUT ROWID;
+    CREATE INDEX IF NOT EXISTS samples_1m_start ON samples_1m (start);
+    CREATE TABLE IF NOT EXISTS samples_1h (
+        series_id INTEGER NOT NULL,
+        start INTEGER NOT NULL,
+        min REAL NOT NULL,
+        avg REAL NOT NULL,
+        max REAL NOT NULL,
+        count INTEGER NOT NULL,
+        PRIMARY KEY (series_id, start)
+    ) WITHOUT ROWID;
+    CREATE INDEX IF NOT EXISTS samples_1h_start ON samples_1h (start);
+    CREATE TABLE IF NOT EXISTS meta (
+        key TEXT PRIMARY KEY,
+        value INTEGER NOT NULL
+    );
+";
+
+#[derive(Error, Debug)]
+pub enum StoreError {
+    #[error("Cannot create data directory '{0}': {1}")]
+    DataDir(String, #[source] io::Error),
+    #[error("Database error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+}
+
+// How long each resolution is kept
+#[derive(Debug, Clone, Copy)]
+pub struct StoreRetention {
+    pub raw: Duration,
+    pub minute: Duration,
+    pub hour: Duration,
+}
+
+// One resolution of the stored data
+#[derive(Debug, Clone, Copy)]
+struct Tier {
+    table: &'static str,
+    // Column holding the sample time or bucket start
+    time_column: &'static str,
+    resolution_ms: u64,
+    // Meta key of the time up to which this tier has been filled from the
+    // finer one; the raw tier is always up to date
+    rolled_key: Option<&'static str>,
+}
+
+// Coarsest first
+const TIERS: [Tier; 3] = [
+    Tier {
+        table: "samples_1h",
+        time_column: "start",
+        resolution_ms: HOUR_MS,
+        rolled_key: Some("rolled_1h_until"),
+    },
+    Tier {
+        table: "samples_1m",
+        time_column: "start",
+        resolution_ms: MINUTE_MS,
+        rolled_key: Some("rolled_1m_until"),
+    },
+    Tier {
+        table: "samples_raw",
+        time_column: "at",
+        resolution_ms: 1,
+        rolled_key: None,
+    },
+];
+
+#[derive(Debug)]
+struct PendingSample {
+    section: Section,
+    name: String,
+    at: u64,
+    value: f64,
+}
+
+struct StoreInner {
+    conn: Connection,
+    // Keyed by (section, series name)
+    series_ids: HashMap<(Section, String), i64>,
+}
+
+pub struct Store {
+    path: PathBuf,
+    retention: StoreRetention,
+    top_processes: usize,
+    inner: Mutex<StoreInner>,
+    // Samples recorded since the last flush, written in one transaction
+    pending: Mutex<Vec<PendingSample>>,
+}
+
+impl std::fmt::Debug for Store {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Store").field("path", &self.path).finish()
+    }
+}
+
+fn get_meta(conn: &Connection, key: &str) -> Result<u64, rusqlite::Error> {
+    let value: Option<i64> = conn
+        .query_row("SELECT value FROM meta WHERE key = ?1", [key], |row| {
+            row.get(0)
+        })
+        .optional()?;
+    Ok(value.unwrap_or(0) as u64)
+}
+
+fn set_meta(conn: &Connection, key: &str, value: u64) -> Result<(), rusqlite::Error> {
+    conn.execute(
+        "INSERT INTO meta (key, value) VALUES (?1, ?2)
+         ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+        params![key, value as i64],
+    )?;
+    Ok(())
+}
+
+// CPU and memory use of the busiest processes: the top `count` by CPU plus
+// the top `count` by resident memory. Recording every process would grow the
+// database with each short-lived command.
+fn process_values(status: &SystemStatus, count: usize) -> Vec<(String, f64)> {
+    let processes = &status.processes.processes;
+    let mut by_cpu: Vec<usize> = (0..processes.len()).collect();
+    by_cpu.sort_by(|&a, &b| {
+        let cpu = |i: usize| processes[i].cpu_percent.unwrap_or(0.0);
+        cpu(b).total_cmp(&cpu(a))
+    });
+    let mut by_memory: Vec<usize> = (0..processes.len()).collect();
+    by_memory.sort_by_key(|&i| std::cmp::Reverse(processes[i].memory_rss.unwrap_or(0)));
+
+    let mut chosen: Vec<usize> = by_cpu.into_iter().take(count).collect();
+    for i in by_memory.into_iter().take(count) {
+        if !chosen.contains(&i) {
+            chosen.push(i);
+        }
+    }
+
+    let mut values = Vec::new();
+    for i in chosen {
+        let process = &processes[i];
+        let Some(pid) = process.pid else {
+            continue;
+        };
+        let prefix = format!("{}:{}", pid, process.name.as_deref().unwrap_or_default());
+        if let Some(cpu) = process.cpu_percent {
+            values.push((format!("{}.cpu_percent", prefix), from_f32(cpu)));
+        }
+        if let Some(rss) = process.memory_rss {
+            values.push((format!("{}.memory_rss", prefix), rss as f64));
+        }
+    }
+    values
+}
+
+impl StoreInner {
+    fn series_id(&mut self, section: Section, name: &str) -> Result<i64, rusqlite::Error> {
+        if let Some(&id) = self.series_ids.get(&(section, name.to_string())) {
+            return Ok(id);
+        }
+        self.conn.execute(
+            "INSERT OR IGNORE INTO series (section, name) VALUES (?1, ?2)",
+            params![section.as_str(), name],
+        )?;
+        let id = self.conn.query_row(
+            "SELECT id FROM series WHERE section = ?1 AND name = ?2",
+            params![section.as_str(), name],
+            |row| row.get(0),
+        )?;
+        self.series_ids.insert((section, name.to_string()), id);
+        Ok(id)
+    }
+
+    // Time of the oldest row of `tier`. Retention applies to every section
+    // alike, so this uses the index on time instead of scanning per section.
+    fn oldest(&self, tier: &Tier) -> Result<Option<u64>, rusqlite::Error> {
+        let sql = format!("SELECT min({}) FROM {}", tier.time_column, tier.table);
+        let oldest: Option<i64> = self.conn.query_row(&sql, [], |row| row.get(0))?;
+        Ok(oldest.map(|at| at as u64))
+    }
+
+    // Time up to which `tier` holds every sample.
+    fn filled_until(&self, tier: &Tier) -> Result<u64, rusqlite::Error> {
+        match tier.rolled_key {
+            Some(key) => get_meta(&self.conn, key),
+            None => Ok(u64::MAX),
+        }
+    }
+}
+
+impl Store {
+    pub fn open(
+        data_dir: &Path,
+        retention: StoreRetention,
+        top_processes: usize,
+    ) -> Result<Self, StoreError> {
+        fs::create_dir_all(data_dir)
+            .map_err(|e| StoreError::DataDir(data_dir.to_string_lossy().into_owned(), e))?;
+        let path = data_dir.join("metrics.db");
+        let conn = Connection::open(&path)?;
+        // auto_vacuum only takes effect before the first table is created;
+        // WAL with NORMAL sync keeps the writes per flush few and sequential,
+        // which matters on an SD card
+        conn.execute_batch(
+            "PRAGMA auto_vacuum = INCREMENTAL;
+             PRAGMA journal_mode = WAL;
+             PRAGMA synchronous = NORMAL;",
+        )?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(Store {
+            path,
+            retention,
+            top_processes,
+            inner: Mutex::new(StoreInner {
+                conn,
+                series_ids: HashMap::new(),
+            }),
+            pending: Mutex::new(Vec::new()),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Queues the refreshed sections of `status` that hold fresh data, like
+    // `History::record`, plus the busiest processes.
+    pub fn record(&self, status: &SystemStatus, sections: &[Section]) {
+        let at = status.collected_at;
+        let mut pending = self.pending.lock().expect("store buffer lock poisoned");
+        for &section in sections {
+            if !is_fresh(status, section) {
+                continue;
+            }
+            let values = if section == Section::Processes {
+                process_values(status, self.top_processes)
+            } else if HISTORY_SECTIONS.contains(&section) {
+                series_values(status, section)
+            } else {
+                continue;
+            };
+            pending.extend(values.into_iter().map(|(name, value)| PendingSample {
+                section,
+                name,
+                at,
+                value,
+            }));
+        }
+    }
+
+    // Writes the queued samples in one transaction.
+    fn flush(&self, inner: &mut StoreInner) -> Result<usize, StoreError> {
+        let pending =
+            std::mem::take(&mut *self.pending.lock().expect("store buffer lock poisoned"));
+        if pending.is_empty() {
+            return Ok(0);
+        }
+        let mut ids = Vec::with_capacity(pending.len());
+        for sample in &pending {
+            ids.push(inner.series_id(sample.section, &sample.name)?);
+        }
+        let tx = inner.conn.transaction()?;
+        {
+            let mut insert = tx.prepare_cached(
+                "INSERT OR REPLACE INTO samples_raw (series_id, at, value) VALUES (?1, ?2, ?3)",
+            )?;
+            for (sample, id) in pending.iter().zip(ids) {
+                insert.execute(params![id, sample.at as i64, sample.value])?;
+            }
+        }
+        tx.commit()?;
+        Ok(pending.len())
+    }
+
+    // Aggregates the complete minutes of raw samples and the complete hours
+    // of minute buckets that have not been rolled up yet.
+    fn roll_up(conn: &mut Connection, now: u64) -> Result<(), rusqlite::Error> {
+        let tx = conn.transaction()?;
+
+        // A minute behind, for samples collected before but recorded after
+        // the previous flush
+        let minute_from = get_meta(&tx, "rolled_1m_until")?;
+        let minute_until = now.saturating_sub(MINUTE_MS) / MINUTE_MS * MINUTE_MS;
+        if minute_until > minute_from {
+            tx.execute(
+                "INSERT OR REPLACE INTO samples_1m (series_id, start, min, avg, max, count)
+                 SELECT series_id, at / ?3 * ?3, min(value), avg(value), max(value), count(*)
+                 FROM samples_raw WHERE at >= ?1 AND at < ?2
+                 GROUP BY series_id, at / ?3",
+                params![minute_from as i64, minute_until as i64, MINUTE_MS as i64],
+            )?;
+            set_meta(&tx, "rolled_1m_until", minute_until)?;
+        }
+
+        let hour_from = get_meta(&tx, "rolled_1h_until")?;
+        let hour_until = minute_until / HOUR_MS * HOUR_MS;
+        if hour_until > hour_from {
+            tx.execute(
+                "INSERT OR REPLACE INTO samples_1h (series_id, start, min, avg, max, count)
+                 SELECT series_id, start / ?3 * ?3, min(min), sum(avg * count) / sum(count),
+                        max(max), sum(count)
+                 FROM samples_1m WHERE start >= ?1 AND start < ?2
+                 GROUP BY series_id, start / ?3",
+                params![hour_from as i64, hour_until as i64, HOUR_MS as i64],
+            )?;
+            set_meta(&tx, "rolled_1h_until", hour_until)?;
+        }
+
+        tx.commit()
+    }
+
+    // Deletes data older than its retention, raw samples and minute buckets
+    // only once they have been rolled up. Returns how many sample rows and
+    // series were deleted.
+    fn apply_retention(
+        &self,
+        conn: &Connection,
+        now: u64,
+    ) -> Result<(usize, usize), rusqlite::Error> {
+        let cutoff = |retention: Duration| now.saturating_sub(retention.as_millis() as u64);
+        // Aligned to the coarser tier, so where finer data ends, whole
+        // coarser buckets begin
+        let raw_cutoff = (cutoff(self.retention.raw) / MINUTE_MS * MINUTE_MS)
+            .min(get_meta(conn, "rolled_1m_until")?);
+        let minute_cutoff = (cutoff(self.retention.minute) / HOUR_MS * HOUR_MS)
+            .min(get_meta(conn, "rolled_1h_until")?);
+        let hour_cutoff = cutoff(self.retention.hour);
+
+        let mut deleted =
+            conn.execute("DELETE FROM samples_raw WHERE at < ?1", [raw_cutoff as i64])?;
+        deleted += conn.execute(
+            "DELETE FROM samples_1m WHERE start < ?1",
+            [minute_cutoff as i64],
+        )?;
+        let deleted_hours = conn.execute(
+            "DELETE FROM samples_1h WHERE start < ?1",
+            [hour_cutoff as i64],
+        )?;
+        deleted += deleted_hours;
+
+        // Every sample ends up in the hour tier before it expires from the
+        // finer ones, so a series can only lose its last row here
+        let mut removed_series = 0;
+        if deleted_hours > 0 {
+            removed_series = conn.execute(
+                "DELETE FROM series WHERE
+                 NOT EXISTS (SELECT 1 FROM samples_raw WHERE series_id = series.id)
+                 AND NOT EXISTS (SELECT 1 FROM samples_1m WHERE series_id = series.id)
+                 AND NOT EXISTS (SELECT 1 FROM samples_1h WHERE series_id = series.id)",
+                [],
+            )?;
+        }
+        Ok((deleted, removed_series))
+    }
+
+    // Flush, roll-up, retention and compaction. Blocks on disk I/O.
+    pub fn maintain(&self) -> Result<(), StoreError> {
+        let now = unix_now_ms();
+        let mut inner = self.inner.lock().expect("store lock poisoned");
+        let written = self.flush(&mut inner)?;
+        Self::roll_up(&mut inner.conn, now)?;
+        let (deleted, removed_series) = self.apply_retention(&inner.conn, now)?;
+        if removed_series > 0 {
+            // Deleted series get new ids when they come back
+            inner.series_ids.clear();
+        }
+        if deleted > 0 {
+            // Returns the pages freed by the deletes to the file system
+            inner.conn.execute_batch("PRAGMA incremental_vacuum;")?;
+        }
+        debug!(
+            "Metric store: wrote {} samples, deleted {} expired rows and {} series",
+            written, deleted, removed_series
+        );
+        Ok(())
+    }
+
+    // Time of the oldest raw sample, if any.
+    pub fn oldest_raw(&self) -> Result<Option<u64>, StoreError> {
+        let inner = self.inner.lock().expect("store lock poisoned");
+        Ok(inner.oldest(&TIERS[2])?)
+    }
+
+    // Raw samples within [from, to] of the given sections, each limited to
+    // its own (section, from, to) range, ordered by time. Blocks on disk I/O.
+    pub fn raw_samples(
+        &self,
+        parts: &[(Section, u64, u64)],
+        from: u64,
+        to: u64,
+    ) -> Result<Vec<(u64, Section, String, f64)>, StoreError> {
+        let mut samples = Vec::new();
+        if parts.is_empty() {
+            return Ok(samples);
+        }
+        let conditions: Vec<String> = (0..parts.len())
+            .map(|i| {
+                format!(
+                    "(s.section = ?{} AND r.at >= ?{} AND r.at <= ?{})",
+                    i * 3 + 3,
+                    i * 3 + 4,
+                    i * 3 + 5
+                )
+            })
+            .collect();
+        let sql = format!(
+            "SELECT r.at, s.section, s.name, r.value
+             FROM samples_raw r JOIN series s ON s.id = r.series_id
+             WHERE r.at >= ?1 AND r.at <= ?2 AND ({})
+             ORDER BY r.at",
+            conditions.join(" OR ")
+        );
+        let mut values: Vec<rusqlite::types::Value> =
+            vec![(from as i64).into(), (to as i64).into()];
+        for &(section, part_from, part_to) in parts {
+            values.push(section.as_str().to_string().into());
+            values.push((part_from as i64).into());
+            values.push((part_to as i64).into());
+        }
+
+        let inner = self.inner.lock().expect("store lock poisoned");
+        let mut statement = inner.conn.prepare_cached(&sql)?;
+        let mut rows = statement.query(rusqlite::params_from_iter(values))?;
+        while let Some(row) = rows.next()? {
+            let at: i64 = row.get(0)?;
+            let section: String = row.get(1)?;
+            // Rows of sections this server no longer knows are skipped
+            let Ok(section) = section.parse::<Section>() else {
+                continue;
+            };
+            samples.push((at as u64, section, row.get(2)?, row.get(3)?));
+        }
+        Ok(samples)
+    }
+
+    // Adds the stored samples of `section` within the range to `buckets`.
+    // Each part of the range is read from the coarsest tier whose resolution
+    // still fits the step, or from a coarser one where the finer data has
+    // already expired. Blocks on disk I/O.
+    pub fn accumulate(
+        &self,
+        section: Section,
+        range: &HistoryRange,
+        buckets: &mut Buckets,
+    ) -> Result<(), StoreError> {
+        let inner = self.inner.lock().expect("store lock poisoned");
+
+        let mut oldest = Vec::with_capacity(TIERS.len());
+        for tier in &TIERS {
+            oldest.push(inner.oldest(tier)?);
+        }
+
+        let mut cursor = range.from;
+        for (i, tier) in TIERS.iter().enumerate() {
+            let mut limit = inner.filled_until(tier)?;
+            if tier.resolution_ms > range.step {
+                let finer_oldest = oldest[i + 1..].iter().flatten().min();
+                limit = limit.min(finer_oldest.copied().unwrap_or(u64::MAX));
+            }
+            // Only buckets that end before the finer tier takes over, so no
+            // sample is counted twice
+            limit = limit / tier.resolution_ms * tier.resolution_ms;
+            let end = range.to.min(limit.saturating_sub(1));
+            if cursor > end {
+                continue;
+            }
+            if tier.rolled_key.is_some() {
+                let sql = format!(
+                    "SELECT s.name, t.start, t.min, t.avg, t.max, t.count
+                     FROM {} t JOIN series s ON s.id = t.series_id
+                     WHERE s.section = ?1 AND t.start > ?2 AND t.start <= ?3",
+                    tier.table
+                );
+                let mut statement = inner.conn.prepare_cached(&sql)?;
+                // Includes the bucket that `cursor` falls into
+                let after = cursor as i64 - tier.resolution_ms as i64;
+                let mut rows = statement.query(params![section.as_str(), after, end as i64])?;
+                while let Some(row) = rows.next()? {
+                    let name: String = row.get(0)?;
+                    if !range.wants(&name) {
+                        continue;
+                    }
+                    let start: i64 = row.get(1)?;
+                    buckets.add_rollup(
+                        &name,
+                        range.bucket_start(start as u64),
+                        row.get::<_, f64>(2)? as f32,
+                        row.get::<_, f64>(3)? as f32,
+                        row.get::<_, f64>(4)? as f32,
+                        row.get::<_, u32>(5)?,
+                    );
+                }
+            } else {
+                let mut statement = inner.conn.prepare_cached(
+                    "SELECT s.name, t.at, t.value
+                     FROM samples_raw t JOIN series s ON s.id = t.series_id
+                     WHERE s.section = ?1 AND t.at >= ?2 AND t.at <= ?3",
+                )?;
+                let mut rows =
+                    statement.query(params![section.as_str(), cursor as i64, end as i64])?;
+                while let Some(row) = rows.next()? {
+                    let name: String = row.get(0)?;
+                    if !range.wants(&name) {
+                        continue;
+                    }
+                    let at: i64 = row.get(1)?;
+                    buckets.add(
+                        &name,
+                        range.bucket_start(at as u64),
+                        row.get::<_, f64>(2)? as f32,
+                    );
+                }
+            }
+            cursor = end.saturating_add(1);
+        }
+        Ok(())
+    }
+}
+
+async fn run_maintenance(store: Arc<Store>) {
+    let result = tokio::task::spawn_blocking(move || store.maintain()).await;
+    match result {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => error!("Metric store maintenance failed: {}", e),
+        Err(e) => error!("Metric store maintenance task failed: {}", e),
+    }
+}
+
+// Writes the queued samples every `interval` and keeps the database within
+// its retention.
+pub async fn run_store_maintenance(store: Arc<Store>, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval.max(Duration::from_secs(1)));
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        run_maintenance(Arc::clone(&store)).await;
+    }
+}
+
+// Writes the queued samples before the process exits on SIGINT or SIGTERM,
+// so a restart loses none of them.
+pub async fn flush_on_shutdown(store: Arc<Store>) {
+    let mut terminate =
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(signal) => signal,
+            Err(e) => {
+                error!("Cannot listen for SIGTERM: {}", e);
+                return;
+            }
+        };
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = terminate.recv() => {}
+    }
+    info!("Shutting down, writing pending samples to the metric store");
+    run_maintenance(store).await;
+    std::process::exit(0);
+}
@@ -0,0 +1,187 @@
+use crate::data_source::ParseError;
+use crate::kernel_fs::read_attribute;
+use crate::models::{
+    CoolingDeviceInfo, HwmonDeviceInfo, HwmonFan, HwmonTemperature, Section, ThermalInfo,
+    ThermalZoneInfo, TripPoint,
+};
+use crate::source::Collection;
+use std::{
+    io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use tokio::fs;
+use tracing::{debug, error};
+
+// Enumerates every on-board sensor under <sysfs>/class: thermal zones with
+// their trip points, hwmon chips with temperatures and fans, and cooling
+// devices.
+
+// Lists the entries of `dir` named `prefix` followed by a number, in numeric
+// order. A missing directory means the kernel has no such devices.
+async fn numbered_entries(dir: &Path, prefix: &str) -> Result<Vec<(u32, PathBuf)>, ParseError> {
+    let mut entries = match fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(ParseError::Io(dir.to_string_lossy().into_owned(), e)),
+    };
+    let mut numbered = Vec::new();
+    while let Some(entry) = entries
+        .next_entry()
+        .await
+        .map_err(|e| ParseError::Io(dir.to_string_lossy().into_owned(), e))?
+    {
+        let name = entry.file_name();
+        let index = name
+            .to_str()
+            .and_then(|name| name.strip_prefix(prefix))
+            .and_then(|index| index.parse::<u32>().ok());
+        if let Some(index) = index {
+            numbered.push((index, entry.path()));
+        }
+    }
+    numbered.sort();
+    Ok(numbered)
+}
+
+// Attributes are optional and some cannot be read in every state (a zone
+// whose sensor is powered down returns EIO), so failures only get logged.
+async fn read_number<T: FromStr>(path: &Path) -> Option<T> {
+    let value = read_attribute(path).await?;
+    match value.parse() {
+        Ok(number) => Some(number),
+        Err(_) => {
+            debug!("{}: not a number: '{}'", path.display(), value);
+            None
+        }
+    }
+}
+
+async fn read_millidegrees(path: &Path) -> Option<f32> {
+    read_number::<i64>(path)
+        .await
+        .map(|milli| milli as f32 / 1000.0)
+}
+
+fn entry_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+async fn read_zone(path: &Path) -> ThermalZoneInfo {
+    let mut trip_points = Vec::new();
+    // Trip points are numbered from 0 without gaps
+    for index in 0.. {
+        let Some(trip_type) =
+            read_attribute(&path.join(format!("trip_point_{}_type", index))).await
+        else {
+            break;
+        };
+        trip_points.push(TripPoint {
+            trip_type,
+            temperature: read_millidegrees(&path.join(format!("trip_point_{}_temp", index))).await,
+        });
+    }
+    ThermalZoneInfo {
+        name: entry_name(path),
+        zone_type: read_attribute(&path.join("type")).await,
+        temperature: read_millidegrees(&path.join("temp")).await,
+        trip_points,
+    }
+}
+
+// Channel numbers present in a hwmon directory for `prefix` ("temp", "fan"),
+// taken from the <prefix><N>_input files.
+async fn hwmon_channels(path: &Path, prefix: &str) -> Vec<u32> {
+    let mut channels = Vec::new();
+    let Ok(mut entries) = fs::read_dir(path).await else {
+        return channels;
+    };
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let name = entry.file_name();
+        let channel = name
+            .to_str()
+            .and_then(|name| name.strip_prefix(prefix))
+            .and_then(|rest| rest.strip_suffix("_input"))
+            .and_then(|index| index.parse::<u32>().ok());
+        if let Some(channel) = channel {
+            channels.push(channel);
+        }
+    }
+    channels.sort_unstable();
+    channels
+}
+
+async fn read_hwmon(path: &Path) -> HwmonDeviceInfo {
+    let mut temperatures = Vec::new();
+    for channel in hwmon_channels(path, "temp").await {
+        let attribute = |suffix: &str| path.join(format!("temp{}_{}", channel, suffix));
+        temperatures.push(HwmonTemperature {
+            name: format!("temp{}", channel),
+            label: read_attribute(&attribute("label")).await,
+            temperature: read_millidegrees(&attribute("input")).await,
+            max: read_millidegrees(&attribute("max")).await,
+            critical: read_millidegrees(&attribute("crit")).await,
+        });
+    }
+
+    let mut fans = Vec::new();
+    for channel in hwmon_channels(path, "fan").await {
+        let attribute = |suffix: &str| path.join(format!("fan{}_{}", channel, suffix));
+        fans.push(HwmonFan {
+            name: format!("fan{}", channel),
+            label: read_attribute(&attribute("label")).await,
+            rpm: read_number(&attribute("input")).await,
+            min_rpm: read_number(&attribute("min")).await,
+        });
+    }
+
+    HwmonDeviceInfo {
+        name: entry_name(path),
+        device_name: read_attribute(&path.join("name")).await,
+        temperatures,
+        fans,
+    }
+}
+
+async fn read_cooling_device(path: &Path) -> CoolingDeviceInfo {
+    CoolingDeviceInfo {
+        name: entry_name(path),
+        device_type: read_attribute(&path.join("type")).await,
+        cur_state: read_number(&path.join("cur_state")).await,
+        max_state: read_number(&path.join("max_state")).await,
+    }
+}
+
+pub async fn read_thermal_info(sysfs_root: &Path) -> Result<ThermalInfo, ParseError> {
+    let thermal_dir = sysfs_root.join("class/thermal");
+    let hwmon_dir = sysfs_root.join("class/hwmon");
+    let mut thermal_info = ThermalInfo::default();
+
+    for (_, path) in numbered_entries(&thermal_dir, "thermal_zone").await? {
+        thermal_info.zones.push(read_zone(&path).await);
+    }
+    for (_, path) in numbered_entries(&hwmon_dir, "hwmon").await? {
+        thermal_info.hwmon.push(read_hwmon(&path).await);
+    }
+    for (_, path) in numbered_entries(&thermal_dir, "cooling_device").await? {
+        thermal_info
+            .cooling_devices
+            .push(read_cooling_device(&path).await);
+    }
+    Ok(thermal_info)
+}
+
+pub async fn collect_thermal(collection: &mut Collection, sysfs_root: &Path) {
+    match read_thermal_info(sysfs_root).await {
+        Ok(thermal_info) => {
+            collection.status.thermal = thermal_info;
+            collection.status.mark_collected(Section::Thermal, None);
+        }
+        Err(e) => {
+            error!("Failed to read thermal sensors: {}", e);
+            collection.record_error(Section::Thermal, &e);
+        }
+    }
+}
@@ -0,0 +1,418 @@
+use crate::clock::unix_now_ms;
+use crate::config::{ParseErrorPolicy, RefreshMode, Settings};
+use crate::models::{
+    FileFindings, Freshness, ParseErrorRecord, Section, SectionFreshness, SystemStatus,
+};
+use crate::source::{Collection, SectionError, StatusSource};
+use crate::usage::{
+    fill_memory_percentages, CpuUsageTracker, DiskStatsTracker, NetworkTracker, ProcessTracker,
+};
+use crate::watcher::FileWatcher;
+use crate::AppState;
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+// Copies one section's data from `from` into `to`.
+fn copy_section(from: &SystemStatus, to: &mut SystemStatus, section: Section) {
+    match section {
+        Section::Cpu => to.cpu = from.cpu.clone(),
+        Section::Memory => to.memory = from.memory.clone(),
+        Section::Processes => to.processes = from.processes.clone(),
+        Section::ExternalTemperature => to.external_temperature = from.external_temperature.clone(),
+        Section::System => to.system = from.system.clone(),
+        Section::Disks => to.disks = from.disks.clone(),
+        Section::Network => to.network = from.network.clone(),
+        Section::Thermal => to.thermal = from.thermal.clone(),
+    }
+}
+
+// Background task refreshing `AppState.system_status` from a status source.
+// Holds the state carried between refreshes that derived values depend on.
+#[derive(Debug)]
+pub struct Updater {
+    source: Arc<dyn StatusSource>,
+    cpu_usage: CpuUsageTracker,
+    processes: ProcessTracker,
+    disk_stats: DiskStatsTracker,
+    network: NetworkTracker,
+    // Interval length from the last CPU read, reused when only the process
+    // list is re-read
+    ticks_per_core: Option<f64>,
+    seq: u64,
+    update_interval: Duration,
+    refresh_mode: RefreshMode,
+    watch_debounce: Duration,
+    watch_fallback: Duration,
+    max_data_age: Duration,
+    parse_error_policy: ParseErrorPolicy,
+    last_freshness: BTreeMap<Section, Freshness>,
+    consecutive_errors: BTreeMap<Section, u32>,
+    // Keyed by file path
+    parse_errors: BTreeMap<String, ParseErrorRecord>,
+    // Line-level findings of the latest read of each file, keyed by path
+    parse_findings: BTreeMap<String, FileFindings>,
+}
+
+impl Updater {
+    pub fn new(source: Arc<dyn StatusSource>, settings: &Settings) -> Self {
+        Updater {
+            source,
+            cpu_usage: CpuUsageTracker::default(),
+            processes: ProcessTracker::default(),
+            disk_stats: DiskStatsTracker::default(),
+            network: NetworkTracker::default(),
+            ticks_per_core: None,
+            seq: 0,
+            update_interval: Duration::from_secs(settings.update_interval_secs),
+            refresh_mode: settings.refresh_mode,
+            watch_debounce: Duration::from_millis(settings.watch_debounce_ms),
+            watch_fallback: Duration::from_secs(settings.watch_fallback_secs),
+            max_data_age: Duration::from_secs(settings.max_data_age_secs),
+            parse_error_policy: settings.parse_error_policy,
+            last_freshness: BTreeMap::new(),
+            consecutive_errors: BTreeMap::new(),
+            parse_errors: BTreeMap::new(),
+            parse_findings: BTreeMap::new(),
+        }
+    }
+
+    // Completes the freshness entries reported by the source: every collected
+    // section gets an entry, and data whose source was not modified within
+    // the max age is downgraded to stale.
+    fn assess_freshness(&self, status: &mut SystemStatus, sections: &[Section]) {
+        let now = status.collected_at;
+        let max_age_ms = self.max_data_age.as_millis() as u64;
+
+        for &section in sections {
+            let entry = status.freshness.entry(section).or_default();
+            if entry.state == Freshness::Fresh {
+                entry.collected_at = Some(now);
+                if let Some(mtime) = entry.source_mtime {
+                    if now.saturating_sub(mtime) > max_age_ms {
+                        entry.state = Freshness::Stale;
+                    }
+                }
+            }
+        }
+    }
+
+    // Updates the per-section and per-file error counters from this
+    // collection's failures. Sections that were not collected keep theirs,
+    // and a section read in part does not count as failed.
+    fn record_errors(
+        &mut self,
+        errors: &[SectionError],
+        failed: &[Section],
+        sections: &[Section],
+        now: u64,
+    ) {
+        for &section in sections {
+            if failed.contains(&section) {
+                *self.consecutive_errors.entry(section).or_insert(0) += 1;
+            } else {
+                self.consecutive_errors.remove(&section);
+            }
+        }
+        for record in self.parse_errors.values_mut() {
+            if sections.contains(&record.section) && !errors.iter().any(|e| e.file == record.file) {
+                record.consecutive_errors = 0;
+            }
+        }
+
+        for error in errors {
+            let record = self
+                .parse_errors
+                .entry(error.file.clone())
+                .or_insert_with(|| ParseErrorRecord {
+                    file: error.file.clone(),
+                    section: error.section,
+                    message: String::new(),
+                    last_error_at: now,
+                    error_count: 0,
+                    consecutive_errors: 0,
+                });
+            record.message = error.message.clone();
+            record.last_error_at = now;
+            record.error_count += 1;
+            record.consecutive_errors += 1;
+        }
+    }
+
+    // Under the retain policy, puts the previous data back for sections that
+    // failed this time, marked stale. Freshness metadata keeps the times of
+    // the last successful read.
+    fn retain_failed_sections(&self, previous: &SystemStatus, status: &mut SystemStatus) {
+        for (&section, &count) in &self.consecutive_errors {
+            let retained = previous
+                .freshness
+                .get(&section)
+                .filter(|_| self.parse_error_policy == ParseErrorPolicy::Retain)
+                .filter(|entry| entry.state != Freshness::Missing)
+                .cloned();
+
+            let entry = match retained {
+                Some(mut entry) => {
+                    copy_section(previous, status, section);
+                    entry.state = Freshness::Stale;
+                    entry
+                }
+                None => status.freshness.remove(&section).unwrap_or_default(),
+            };
+            status.freshness.insert(
+                section,
+                SectionFreshness {
+                    consecutive_errors: Some(count),
+                    ..entry
+                },
+            );
+        }
+    }
+
+    // Under the retain policy, sensors that failed while others were read
+    // keep their previous values; `error` and `read_at` tell them apart.
+    fn retain_failed_sensors(&self, previous: &SystemStatus, status: &mut SystemStatus) {
+        if self.parse_error_policy != ParseErrorPolicy::Retain {
+            return;
+        }
+        let ext_temp = &mut status.external_temperature;
+        for sensor in ext_temp.sensors.iter_mut().filter(|s| s.error.is_some()) {
+            let earlier = previous
+                .external_temperature
+                .sensors
+                .iter()
+                .find(|s| s.name == sensor.name);
+            if let Some(earlier) = earlier {
+                sensor.temperature = earlier.temperature;
+                sensor.humidity = earlier.humidity;
+                sensor.read_at = earlier.read_at;
+            }
+        }
+        ext_temp.temperature = ext_temp.sensors.first().and_then(|s| s.celsius());
+    }
+
+    fn log_freshness_changes(&mut self, status: &SystemStatus) {
+        for (&section, entry) in &status.freshness {
+            let previous = self.last_freshness.insert(section, entry.state);
+            if previous == Some(entry.state) {
+                continue;
+            }
+            match entry.state {
+                Freshness::Fresh => info!("Section '{}' is fresh", section),
+                Freshness::Stale if entry.consecutive_errors.is_some() => warn!(
+                    "Section '{}' is stale: keeping last good data after a read error",
+                    section
+                ),
+                Freshness::Stale => warn!(
+                    "Section '{}' is stale: source not updated for more than {} s",
+                    section,
+                    self.max_data_age.as_secs()
+                ),
+                Freshness::Missing => warn!("Section '{}' is missing", section),
+            }
+        }
+    }
+
+    async fn refresh(&mut self, state: &RwLock<AppState>, sections: &[Section]) {
+        if sections.len() == Section::ALL.len() {
+            info!(
+                "Background task: Updating system status from {}...",
+                self.source.name()
+            );
+        } else {
+            info!(
+                "Background task: Updating {} from {}...",
+                sections
+                    .iter()
+                    .map(Section::as_str)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                self.source.name()
+            );
+        }
+
+        let capabilities = self.source.capabilities();
+        let collection = self.source.collect_sections(sections).await;
+        let failed_cpu = collection.failed(Section::Cpu);
+        let failed_processes = collection.failed(Section::Processes);
+        let failed_disks = collection.failed(Section::Disks);
+        let failed_network = collection.failed(Section::Network);
+        let failed: Vec<Section> = sections
+            .iter()
+            .copied()
+            .filter(|&section| collection.failed(section))
+            .collect();
+        let Collection {
+            status: mut new_status,
+            errors,
+            findings,
+        } = collection;
+
+        self.seq += 1;
+        new_status.seq = self.seq;
+        new_status.collected_at = unix_now_ms();
+        self.assess_freshness(&mut new_status, sections);
+        self.record_errors(&errors, &failed, sections, new_status.collected_at);
+        self.parse_findings
+            .retain(|_, file| !sections.contains(&file.section));
+        for file in findings {
+            self.parse_findings.insert(file.file.clone(), file);
+        }
+
+        // Trackers keep their baseline across a failed read instead of
+        // treating every process as exited
+        if sections.contains(&Section::Cpu) && !failed_cpu {
+            self.ticks_per_core = self
+                .cpu_usage
+                .apply(&mut new_status.cpu, capabilities.cpu_counters);
+            let source_mtime = new_status
+                .freshness
+                .get(&Section::Cpu)
+                .and_then(|entry| entry.source_mtime);
+            self.cpu_usage
+                .accumulate(&new_status.cpu, capabilities.cpu_counters, source_mtime);
+        }
+        if sections.contains(&Section::Memory) {
+            fill_memory_percentages(&mut new_status.memory);
+        }
+        if sections.contains(&Section::Processes) && !failed_processes {
+            self.processes.apply(
+                &mut new_status.processes,
+                capabilities.cpu_counters,
+                self.ticks_per_core,
+            );
+        }
+        if sections.contains(&Section::Disks) && !failed_disks {
+            self.disk_stats.apply(&mut new_status.disks);
+        }
+        if sections.contains(&Section::Network) && !failed_network {
+            self.network.apply(&mut new_status.network);
+        }
+
+        {
+            let mut state_guard = state.write().await;
+            let previous = &state_guard.system_status;
+            for section in Section::ALL {
+                if !sections.contains(&section) {
+                    copy_section(previous, &mut new_status, section);
+                    if let Some(entry) = previous.freshness.get(&section) {
+                        new_status.freshness.insert(section, entry.clone());
+                    }
+                }
+            }
+            if sections.contains(&Section::ExternalTemperature)
+                && !failed.contains(&Section::ExternalTemperature)
+            {
+                self.retain_failed_sensors(&state_guard.system_status, &mut new_status);
+            }
+            self.retain_failed_sections(&state_guard.system_status, &mut new_status);
+            self.log_freshness_changes(&new_status);
+            state_guard.history.record(&new_status, sections);
+            if let Some(store) = &state_guard.store {
+                store.record(&new_status, sections);
+            }
+            let collected: Vec<Section> = new_status
+                .freshness
+                .iter()
+                .filter(|(_, entry)| entry.state != Freshness::Missing)
+                .map(|(&section, _)| section)
+                .collect();
+            state_guard.collected_sections.extend(collected);
+            state_guard.system_status = new_status;
+            if state_guard.status_updates.receiver_count() > 0 {
+                let snapshot = Arc::new(state_guard.system_status.clone());
+                state_guard.status_updates.send_replace(snapshot);
+            }
+            state_guard.cpu_totals = self.cpu_usage.totals().clone();
+            state_guard.parse_errors = self.parse_errors.values().cloned().collect();
+            state_guard.parse_diagnostics.files = self.parse_findings.values().cloned().collect();
+        }
+        info!("Background task: System status update complete.");
+    }
+
+    fn start_watcher(&self) -> Option<FileWatcher> {
+        let files = self.source.watched_files();
+        if files.is_empty() {
+            warn!(
+                "Status source '{}' has no files to watch, polling every {} s instead",
+                self.source.name(),
+                self.update_interval.as_secs()
+            );
+            return None;
+        }
+        match FileWatcher::new(&files) {
+            Ok(watcher) => {
+                info!(
+                    "Watching status files for changes (debounce {} ms, full re-read every {} s)",
+                    self.watch_debounce.as_millis(),
+                    self.watch_fallback.as_secs()
+                );
+                Some(watcher)
+            }
+            Err(e) => {
+                warn!(
+                    "Cannot watch status files: {}. Polling every {} s instead",
+                    e,
+                    self.update_interval.as_secs()
+                );
+                None
+            }
+        }
+    }
+
+    pub async fn run(mut self, state: Arc<RwLock<AppState>>) {
+        let mut watcher = match self.refresh_mode {
+            RefreshMode::Watch => self.start_watcher(),
+            RefreshMode::Poll => None,
+        };
+        let watched: Vec<Section> = self
+            .source
+            .watched_files()
+            .into_iter()
+            .map(|(section, _)| section)
+            .collect();
+        let capabilities = self.source.capabilities();
+        // Sections not backed by a status file (disks, network, thermal) get
+        // no notification and are re-read along with every change
+        let unwatched: Vec<Section> = Section::ALL
+            .into_iter()
+            .filter(|section| capabilities.provides(*section) && !watched.contains(section))
+            .collect();
+        // In watch mode the timer only catches missed notifications and ages
+        // data that is no longer being written
+        let mut interval = tokio::time::interval(match watcher {
+            Some(_) => self.watch_fallback,
+            None => self.update_interval,
+        });
+
+        loop {
+            let changes = match watcher.as_mut() {
+                None => {
+                    interval.tick().await;
+                    Some(Section::ALL.to_vec())
+                }
+                Some(watcher) => tokio::select! {
+                    _ = interval.tick() => Some(Section::ALL.to_vec()),
+                    changed = watcher.next_changes(self.watch_debounce) => {
+                        changed.map(|mut sections| {
+                            sections.extend(unwatched.iter().copied());
+                            sections.into_iter().collect()
+                        })
+                    }
+                },
+            };
+
+            match changes {
+                Some(sections) => self.refresh(&state, &sections).await,
+                None => {
+                    warn!(
+                        "File watching stopped, polling every {} s instead",
+                        self.update_interval.as_secs()
+                    );
+                    watcher = None;
+                    interval = tokio::time::interval(self.update_interval);
+                }
+            }
+        }
+    }
+}
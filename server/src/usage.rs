@@ -0,0 +1,431 @@
+use crate::clock::unix_now_ms;
+use crate::models::{
+    BlockDeviceInfo, CpuInfo, CpuStat, DisksInfo, MemoryInfo, NetworkInfo, NetworkInterfaceInfo,
+    ProcessesInfo,
+};
+use crate::source::CounterKind;
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::Instant,
+};
+use tracing::debug;
+
+// Raw jiffy counters of one CPU line in a fixed order:
+// user, nice, kernel, idle, iowait, irq, soft irq.
+pub type Counters = [u64; 7];
+
+fn counters(stat: &CpuStat) -> Option<Counters> {
+    Some([
+        stat.user_norm?,
+        stat.user_nice?,
+        stat.kernel?,
+        stat.idle?,
+        stat.iowait?,
+        stat.irq?,
+        stat.soft_irq?,
+    ])
+}
+
+// Counters kept in 32 bits wrap to zero at this value
+const WRAP_32: u64 = 1 << 32;
+
+// The difference of one counter. A counter that went backwards from the top
+// quarter of the 32-bit range to the bottom quarter wrapped around; any other
+// decrease is a reset (a restarted producer or a core that came back online)
+// and gives None.
+fn single_delta(current: u64, previous: u64) -> Option<u64> {
+    if let Some(delta) = current.checked_sub(previous) {
+        return Some(delta);
+    }
+    let wrapped = (WRAP_32 / 4 * 3..WRAP_32).contains(&previous) && current < WRAP_32 / 4;
+    wrapped.then(|| WRAP_32 - previous + current)
+}
+
+// Returns None when any counter was reset, so that the whole sample is
+// skipped and the next one is measured against a new baseline.
+fn counter_delta(current: &Counters, previous: &Counters) -> Option<Counters> {
+    let mut delta = [0u64; 7];
+    for (i, d) in delta.iter_mut().enumerate() {
+        *d = single_delta(current[i], previous[i])?;
+    }
+    Some(delta)
+}
+
+fn fill_percentages(stat: &mut CpuStat, delta: &Counters) {
+    let total: u64 = delta.iter().sum();
+    if total == 0 {
+        // No time elapsed for this CPU, nothing meaningful to report
+        return;
+    }
+    let percent = |ticks: u64| Some((ticks as f64 * 100.0 / total as f64) as f32);
+    let [user, nice, kernel, idle, iowait, irq, soft_irq] = *delta;
+
+    stat.usage_percent = percent(total - idle - iowait);
+    stat.user_percent = percent(user);
+    stat.nice_percent = percent(nice);
+    stat.kernel_percent = percent(kernel);
+    stat.idle_percent = percent(idle);
+    stat.iowait_percent = percent(iowait);
+    stat.irq_percent = percent(irq);
+    stat.soft_irq_percent = percent(soft_irq);
+}
+
+// Keeps the previous CPU sample so that utilisation can be derived from
+// cumulative counters. Interval counters are converted directly.
+#[derive(Debug, Default)]
+pub struct CpuUsageTracker {
+    previous_full: Option<Counters>,
+    previous_cores: HashMap<u32, Counters>,
+    // Per-core jiffies that only go up, for the counters of GET /metrics
+    totals: BTreeMap<u32, Counters>,
+    // Modification time of the CPU file whose interval was added last
+    totals_mtime: Option<u64>,
+}
+
+impl CpuUsageTracker {
+    // Returns the counter delta of `stat` against `previous` and records the
+    // new sample.
+    fn delta_for(
+        kind: CounterKind,
+        stat: &CpuStat,
+        previous: Option<Counters>,
+        label: &str,
+    ) -> (Option<Counters>, Option<Counters>) {
+        let Some(current) = counters(stat) else {
+            return (None, None);
+        };
+        let delta = match kind {
+            CounterKind::Interval => Some(current),
+            CounterKind::Cumulative => match previous {
+                Some(previous) => {
+                    let delta = counter_delta(&current, &previous);
+                    if delta.is_none() {
+                        debug!("CPU {}: counters were reset, resetting baseline", label);
+                    }
+                    delta
+                }
+                // First sample or a newly plugged core
+                None => None,
+            },
+        };
+        (delta, Some(current))
+    }
+
+    // Fills the percentage fields and returns the jiffies one core spent over
+    // the interval, the time base for per-process CPU shares.
+    pub fn apply(&mut self, cpu: &mut CpuInfo, kind: CounterKind) -> Option<f64> {
+        let Some(usage) = cpu.cpu_usage.as_mut() else {
+            self.previous_full = None;
+            self.previous_cores.clear();
+            return None;
+        };
+
+        let mut full_ticks = None;
+        self.previous_full = match usage.full.as_mut() {
+            Some(full) => {
+                let (delta, current) = Self::delta_for(kind, full, self.previous_full, "full");
+                if let Some(delta) = delta {
+                    fill_percentages(full, &delta);
+                    full_ticks = Some(delta.iter().sum::<u64>());
+                }
+                current
+            }
+            None => None,
+        };
+
+        // Rebuilt every time so that cores which went offline are forgotten
+        let mut seen_cores = HashMap::new();
+        for core in usage.cores.iter_mut().flatten() {
+            let previous = self.previous_cores.get(&core.core_id).copied();
+            let label = format!("core {}", core.core_id);
+            let (delta, current) = Self::delta_for(kind, &core.stats, previous, &label);
+            if let Some(delta) = delta {
+                fill_percentages(&mut core.stats, &delta);
+            }
+            if let Some(current) = current {
+                seen_cores.insert(core.core_id, current);
+            }
+        }
+        self.previous_cores = seen_cores;
+
+        let core_count = usage.cores.as_ref().map_or(1, |cores| cores.len().max(1));
+        full_ticks
+            .filter(|&ticks| ticks > 0)
+            .map(|ticks| ticks as f64 / core_count as f64)
+    }
+
+    // Updates the per-core totals: the source's own counters when they are
+    // cumulative, otherwise a running sum of the intervals since the server
+    // started. An interval is added once per write of the producer, told
+    // apart by the file's modification time, so re-reading an unchanged
+    // file does not count it twice.
+    pub fn accumulate(&mut self, cpu: &CpuInfo, kind: CounterKind, source_mtime: Option<u64>) {
+        let cores = cpu
+            .cpu_usage
+            .as_ref()
+            .and_then(|usage| usage.cores.as_ref())
+            .into_iter()
+            .flatten();
+        match kind {
+            CounterKind::Cumulative => {
+                self.totals = cores
+                    .filter_map(|core| Some((core.core_id, counters(&core.stats)?)))
+                    .collect();
+            }
+            CounterKind::Interval => {
+                if source_mtime.is_some() && source_mtime == self.totals_mtime {
+                    return;
+                }
+                self.totals_mtime = source_mtime;
+                for core in cores {
+                    let Some(interval) = counters(&core.stats) else {
+                        continue;
+                    };
+                    let total = self.totals.entry(core.core_id).or_default();
+                    for (total, ticks) in total.iter_mut().zip(interval) {
+                        *total = total.saturating_add(ticks);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn totals(&self) -> &BTreeMap<u32, Counters> {
+        &self.totals
+    }
+}
+
+fn share_percent(part: u64, whole: u64) -> Option<f32> {
+    (whole > 0).then(|| (part as f64 * 100.0 / whole as f64) as f32)
+}
+
+// Fills the derived memory percentages. Memory counts as used unless it is
+// available; producers without MemAvailable fall back to free plus the
+// reclaimable buffers and page cache.
+pub fn fill_memory_percentages(memory: &mut MemoryInfo) {
+    memory.used_percent = memory.total.and_then(|total| {
+        let available = memory.available.or_else(|| {
+            Some(memory.free? + memory.buffers.unwrap_or(0) + memory.cached.unwrap_or(0))
+        })?;
+        share_percent(total.saturating_sub(available), total)
+    });
+    memory.swap_used_percent = memory
+        .swap_total
+        .zip(memory.swap_free)
+        .and_then(|(total, free)| share_percent(total.saturating_sub(free), total));
+}
+
+// A PID alone is not a stable identity because PIDs are reused. The start
+// time tells two processes with the same PID apart; sources that do not
+// report it fall back to the process name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum ProcessKey {
+    Started(u32, u64),
+    Named(u32, Option<String>),
+}
+
+#[derive(Debug)]
+struct TrackedProcess {
+    first_seen: u64,
+    utime: Option<u64>,
+    memory_rss: Option<u64>,
+}
+
+// Follows processes across refreshes to turn cumulative counters into rates.
+#[derive(Debug, Default)]
+pub struct ProcessTracker {
+    processes: HashMap<ProcessKey, TrackedProcess>,
+    last_refresh: Option<Instant>,
+}
+
+impl ProcessTracker {
+    // `ticks_per_core` is the length of the refresh interval in CPU jiffies,
+    // as returned by `CpuUsageTracker::apply`.
+    pub fn apply(
+        &mut self,
+        processes: &mut ProcessesInfo,
+        kind: CounterKind,
+        ticks_per_core: Option<f64>,
+    ) {
+        let now = Instant::now();
+        let now_ms = unix_now_ms();
+        let elapsed_secs = self
+            .last_refresh
+            .map(|last| now.duration_since(last).as_secs_f64())
+            .filter(|&secs| secs > 0.0);
+
+        let mut seen = HashMap::with_capacity(processes.processes.len());
+        for proc in processes.processes.iter_mut() {
+            let Some(pid) = proc.pid else {
+                continue;
+            };
+            let key = match proc.start_time {
+                Some(start_time) => ProcessKey::Started(pid, start_time),
+                None => ProcessKey::Named(pid, proc.name.clone()),
+            };
+            let previous = self.processes.remove(&key);
+
+            let utime_delta = match kind {
+                CounterKind::Interval => proc.utime,
+                CounterKind::Cumulative => previous
+                    .as_ref()
+                    .and_then(|p| p.utime)
+                    .zip(proc.utime)
+                    .and_then(|(before, now)| now.checked_sub(before)),
+            };
+            proc.cpu_percent = utime_delta
+                .zip(ticks_per_core)
+                .map(|(ticks, per_core)| (ticks as f64 * 100.0 / per_core) as f32);
+
+            proc.memory_rss_rate = previous
+                .as_ref()
+                .and_then(|p| p.memory_rss)
+                .zip(proc.memory_rss)
+                .zip(elapsed_secs)
+                .map(|((before, now), secs)| (now as f64 - before as f64) / secs);
+
+            let first_seen = previous.map_or(now_ms, |p| p.first_seen);
+            proc.first_seen = Some(first_seen);
+
+            seen.insert(
+                key,
+                TrackedProcess {
+                    first_seen,
+                    utime: proc.utime,
+                    memory_rss: proc.memory_rss,
+                },
+            );
+        }
+
+        // Whatever was not seen again has exited
+        self.processes = seen;
+        self.last_refresh = Some(now);
+    }
+}
+
+// Per-second rates of cumulative counters. None when any counter went
+// backwards, as happens when a device or interface is re-created.
+fn counter_rates<const N: usize>(
+    current: &[u64; N],
+    previous: &[u64; N],
+    secs: f64,
+) -> Option<[f64; N]> {
+    let mut rates = [0.0; N];
+    for (i, rate) in rates.iter_mut().enumerate() {
+        *rate = current[i].checked_sub(previous[i])? as f64 / secs;
+    }
+    Some(rates)
+}
+
+fn elapsed_since(last_refresh: Option<Instant>, now: Instant) -> Option<f64> {
+    last_refresh
+        .map(|last| now.duration_since(last).as_secs_f64())
+        .filter(|&secs| secs > 0.0)
+}
+
+// Cumulative counters of one device: reads, sectors read, writes, sectors
+// written, I/O time in ms.
+type DiskCounters = [u64; 5];
+
+fn disk_counters(device: &BlockDeviceInfo) -> DiskCounters {
+    [
+        device.reads_completed,
+        device.sectors_read,
+        device.writes_completed,
+        device.sectors_written,
+        device.io_time_ms,
+    ]
+}
+
+// Keeps the previous diskstats sample to derive per-second rates.
+#[derive(Debug, Default)]
+pub struct DiskStatsTracker {
+    previous: HashMap<String, DiskCounters>,
+    last_refresh: Option<Instant>,
+}
+
+impl DiskStatsTracker {
+    pub fn apply(&mut self, disks: &mut DisksInfo) {
+        let now = Instant::now();
+        let elapsed_secs = elapsed_since(self.last_refresh, now);
+
+        let mut seen = HashMap::with_capacity(disks.devices.len());
+        for device in disks.devices.iter_mut() {
+            let current = disk_counters(device);
+            let rates = self
+                .previous
+                .get(&device.name)
+                .zip(elapsed_secs)
+                .and_then(|(previous, secs)| counter_rates(&current, previous, secs));
+            if let Some([reads, sectors_read, writes, sectors_written, io_ms]) = rates {
+                device.reads_per_sec = Some(reads);
+                device.read_bytes_per_sec = Some(sectors_read * 512.0);
+                device.writes_per_sec = Some(writes);
+                device.write_bytes_per_sec = Some(sectors_written * 512.0);
+                // Milliseconds busy per second, as a percentage
+                device.busy_percent = Some((io_ms / 10.0).min(100.0) as f32);
+            }
+            seen.insert(device.name.clone(), current);
+        }
+
+        self.previous = seen;
+        self.last_refresh = Some(now);
+    }
+}
+
+// rx bytes, packets, errors, dropped, then the same for tx
+type NetworkCounters = [u64; 8];
+
+fn network_counters(interface: &NetworkInterfaceInfo) -> NetworkCounters {
+    [
+        interface.rx_bytes,
+        interface.rx_packets,
+        interface.rx_errors,
+        interface.rx_dropped,
+        interface.tx_bytes,
+        interface.tx_packets,
+        interface.tx_errors,
+        interface.tx_dropped,
+    ]
+}
+
+// Keeps the previous interface counters to derive per-second rates.
+#[derive(Debug, Default)]
+pub struct NetworkTracker {
+    previous: HashMap<String, NetworkCounters>,
+    last_refresh: Option<Instant>,
+}
+
+impl NetworkTracker {
+    pub fn apply(&mut self, network: &mut NetworkInfo) {
+        let now = Instant::now();
+        let elapsed_secs = elapsed_since(self.last_refresh, now);
+
+        let mut seen = HashMap::with_capacity(network.interfaces.len());
+        for interface in network.interfaces.iter_mut() {
+            let current = network_counters(interface);
+            let rates = self
+                .previous
+                .get(&interface.name)
+                .zip(elapsed_secs)
+                .and_then(|(previous, secs)| counter_rates(&current, previous, secs));
+            if let Some(
+                [rx_bytes, rx_packets, rx_errors, rx_dropped, tx_bytes, tx_packets, tx_errors, tx_dropped],
+            ) = rates
+            {
+                interface.rx_bytes_per_sec = Some(rx_bytes);
+                interface.rx_packets_per_sec = Some(rx_packets);
+                interface.rx_errors_per_sec = Some(rx_errors);
+                interface.rx_dropped_per_sec = Some(rx_dropped);
+                interface.tx_bytes_per_sec = Some(tx_bytes);
+                interface.tx_packets_per_sec = Some(tx_packets);
+                interface.tx_errors_per_sec = Some(tx_errors);
+                interface.tx_dropped_per_sec = Some(tx_dropped);
+            }
+            seen.insert(interface.name.clone(), current);
+        }
+
+        self.previous = seen;
+        self.last_refresh = Some(now);
+    }
+}
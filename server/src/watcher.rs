@@ -0,0 +1,119 @@
+use crate::models::Section;
+use futures_util::StreamExt;
+use inotify::{EventMask, EventStream, Inotify, WatchDescriptor, WatchMask};
+use std::{
+    collections::{BTreeSet, HashMap},
+    ffi::OsString,
+    io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tracing::{debug, warn};
+
+// Watches the directories of the status files and reports which sections were
+// rewritten. Directories are watched instead of the files themselves so that
+// a writer replacing a file by renaming a temporary one over it is seen too.
+pub struct FileWatcher {
+    events: EventStream<[u8; 4096]>,
+    // File name to section, per watched directory
+    files: HashMap<WatchDescriptor, HashMap<OsString, Section>>,
+}
+
+impl std::fmt::Debug for FileWatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FileWatcher")
+            .field("directories", &self.files.len())
+            .finish()
+    }
+}
+
+impl FileWatcher {
+    pub fn new(files: &[(Section, PathBuf)]) -> io::Result<Self> {
+        let inotify = Inotify::init()?;
+        let mut watched = HashMap::<WatchDescriptor, HashMap<OsString, Section>>::new();
+
+        for (section, path) in files {
+            let Some(name) = path.file_name() else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("'{}' does not name a file", path.display()),
+                ));
+            };
+            let dir = match path.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir,
+                _ => Path::new("."),
+            };
+            // Adding the same directory twice returns the same descriptor
+            let wd = inotify
+                .watches()
+                .add(dir, WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO)
+                .map_err(|e| {
+                    io::Error::new(e.kind(), format!("watching '{}': {}", dir.display(), e))
+                })?;
+            watched
+                .entry(wd)
+                .or_default()
+                .insert(name.to_os_string(), *section);
+        }
+
+        Ok(FileWatcher {
+            events: inotify.into_event_stream([0u8; 4096])?,
+            files: watched,
+        })
+    }
+
+    // Waits for a status file to be written, then keeps collecting changes
+    // until none arrived for `debounce`, so that a producer writing all files
+    // in one go causes a single refresh. Returns None once the event stream
+    // has failed; the caller is expected to fall back to polling.
+    pub async fn next_changes(&mut self, debounce: Duration) -> Option<BTreeSet<Section>> {
+        let mut changed = BTreeSet::new();
+        while changed.is_empty() {
+            let event = self.events.next().await?;
+            self.record(event, &mut changed)?;
+        }
+
+        loop {
+            match tokio::time::timeout(debounce, self.events.next()).await {
+                Ok(Some(event)) => self.record(event, &mut changed)?,
+                Ok(None) => return None,
+                Err(_) => return Some(changed),
+            }
+        }
+    }
+
+    fn record(
+        &self,
+        event: io::Result<inotify::Event<OsString>>,
+        changed: &mut BTreeSet<Section>,
+    ) -> Option<()> {
+        let event = match event {
+            Ok(event) => event,
+            Err(e) => {
+                warn!("Reading file change events failed: {}", e);
+                return None;
+            }
+        };
+        if event.mask.contains(EventMask::Q_OVERFLOW) {
+            // Events were dropped, so anything may have changed
+            debug!("File change event queue overflowed");
+            for sections in self.files.values() {
+                changed.extend(sections.values().copied());
+            }
+            return Some(());
+        }
+        if event.mask.contains(EventMask::IGNORED) {
+            warn!("A watched status file directory was removed");
+            return None;
+        }
+        let section = event
+            .name
+            .as_ref()
+            .and_then(|name| self.files.get(&event.wd)?.get(name));
+        if let Some(&section) = section {
+            debug!("Status file for '{}' was written", section);
+            changed.insert(section);
+        }
+        Some(())
+    }
+}